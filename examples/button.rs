@@ -11,7 +11,7 @@ fn main() {
     engine.run(move |ctx, mut audio, pixels| {
         let frame = ctx.current_frame();
         if frame == 0 {
-            beep_channel = Some(audio.add_synth_channel(Box::new([-1.0, -1.0, 1.0])));
+            beep_channel = Some(audio.add_synth_channel(vec![-1.0, -1.0, 1.0]));
         }
 
         pixels.fill([16; 3]);
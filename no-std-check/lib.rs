@@ -0,0 +1,15 @@
+//! Builds micropixel's `raster` and `text` modules on their own without `std`, so anything in
+//! them that needs the standard library fails to compile. Check with
+//! `cargo build --manifest-path no-std-check/Cargo.toml`.
+
+#![no_std]
+
+extern crate alloc;
+
+// Some of it is only used by the rest of micropixel
+#[allow(dead_code)]
+#[path = "../src/raster.rs"]
+pub mod raster;
+
+#[path = "../src/text.rs"]
+pub mod text;
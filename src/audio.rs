@@ -1,6 +1,9 @@
 use std::{
     error::Error,
-    sync::{Arc, Mutex, MutexGuard},
+    sync::{
+        atomic::{AtomicBool, AtomicU32, AtomicU8, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
 };
 
 use cpal::{
@@ -11,6 +14,10 @@ use cpal::{
 
 const MIN_SAMPLE_RATE: u32 = 44100;
 
+// Quiet enough that around twenty channels at full volume can play at once before the mix
+// clips, since channels are simply added together
+pub(crate) const DEFAULT_CHANNEL_VOLUME: f32 = 0.05;
+
 #[derive(PartialEq, Eq, Clone, Copy, Hash, Debug)]
 pub struct AudioChannelId(u32);
 
@@ -20,28 +27,137 @@ impl AudioChannelId {
     }
 }
 
+/// The frequency of `note` in hertz. Notes count semitones from A4, which is 440 Hz, in equal
+/// temperament; they're what [`AudioChannel::play_note`] and the other note functions take.
+pub fn note_to_hz(note: i16) -> f32 {
+    440.0 * 2f32.powf(note as f32 * (1.0 / 12.0))
+}
+
+/// The note closest to `hertz`. The inverse of [`note_to_hz`].
+pub fn hz_to_note(hertz: f32) -> i16 {
+    (12.0 * (hertz / 440.0).log2()).round() as i16
+}
+
+/// Parses a note name like `"A4"`, `"C#5"` or `"Bb3"` into a note for
+/// [`AudioChannel::play_note`], so music can be written by name. The letter can be followed by
+/// any number of `#` for sharps or `b` for flats, then the octave, which starts at C as usual:
+/// `"B3"` is just below `"C4"`, middle C.
+///
+/// # Panics
+///
+/// Panics if `name` isn't a note name.
+pub fn note(name: &str) -> i16 {
+    let mut chars = name.chars();
+    // Semitones from the C in the same octave
+    let letter = match chars.next().map(|c| c.to_ascii_uppercase()) {
+        Some('C') => 0,
+        Some('D') => 2,
+        Some('E') => 4,
+        Some('F') => 5,
+        Some('G') => 7,
+        Some('A') => 9,
+        Some('B') => 11,
+        _ => panic!("invalid note name {name:?}"),
+    };
+    let rest = chars.as_str();
+    let octave_start = rest.find(|c| c != '#' && c != 'b').unwrap_or(rest.len());
+    let (accidentals, octave) = rest.split_at(octave_start);
+    let accidental = accidentals
+        .chars()
+        .map(|c| if c == '#' { 1 } else { -1 })
+        .sum::<i16>();
+    let octave: i16 = octave
+        .parse()
+        .unwrap_or_else(|_| panic!("invalid note name {name:?}"));
+    (octave - 4) * 12 + letter + accidental - 9
+}
+
+/// A musical scale, for keeping generated music in key. Each degree of the scale is a number of
+/// semitones above the root, so `[0, 2, 4, 5, 7, 9, 11]` is a major scale.
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Copy)]
+pub struct Scale<'a> {
+    semitones: &'a [i16],
+}
+
+impl Scale<'static> {
+    pub const MAJOR: Self = Self::new(&[0, 2, 4, 5, 7, 9, 11]);
+    pub const MINOR: Self = Self::new(&[0, 2, 3, 5, 7, 8, 10]);
+    pub const HARMONIC_MINOR: Self = Self::new(&[0, 2, 3, 5, 7, 8, 11]);
+    pub const MAJOR_PENTATONIC: Self = Self::new(&[0, 2, 4, 7, 9]);
+    pub const MINOR_PENTATONIC: Self = Self::new(&[0, 3, 5, 7, 10]);
+    pub const BLUES: Self = Self::new(&[0, 3, 5, 6, 7, 10]);
+    pub const CHROMATIC: Self = Self::new(&[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11]);
+}
+
+impl<'a> Scale<'a> {
+    /// A scale with a degree for each of `semitones`, which repeats every octave.
+    ///
+    /// # Panics
+    ///
+    /// Panics unless `semitones` starts at 0 and rises strictly within one octave, below 12.
+    pub const fn new(semitones: &'a [i16]) -> Self {
+        assert!(
+            !semitones.is_empty() && semitones[0] == 0,
+            "a scale has to start at its root"
+        );
+        let mut i = 1;
+        while i < semitones.len() {
+            assert!(
+                semitones[i] > semitones[i - 1] && semitones[i] < 12,
+                "a scale's semitones have to rise within one octave"
+            );
+            i += 1;
+        }
+        Self { semitones }
+    }
+
+    #[inline]
+    pub fn semitones(&self) -> &'a [i16] {
+        self.semitones
+    }
+
+    /// The note `degree` steps up the scale from `root`, where degree 0 is the root itself.
+    /// Degrees past the end of the scale continue in the octaves above, and negative degrees go
+    /// below the root.
+    pub fn note(&self, root: i16, degree: i16) -> i16 {
+        let len = self.semitones.len() as i16;
+        root + degree.div_euclid(len) * 12 + self.semitones[degree.rem_euclid(len) as usize]
+    }
+}
+
 // Not a good hash but appears random enough
-fn simple_hash(x: u32) -> u32 {
+pub(crate) fn simple_hash(x: u32) -> u32 {
     let x = x.overflowing_mul(x ^ 0x84da2122).0 ^ 0x41b6b602;
-    let x = x.overflowing_mul(x ^ 0x2eecbb95).0 ^ 0x67d37dec;
-    x
+    x.overflowing_mul(x ^ 0x2eecbb95).0 ^ 0x67d37dec
 }
 
 pub struct AudioWrapper<'a> {
     sample_rate: u32,
     channels: Option<MutexGuard<'a, Vec<AudioChannel>>>,
+    master_peak: f32,
+    limiter: Option<&'a AtomicU8>,
+    paused: Option<&'a AtomicBool>,
+    history: Option<&'a Mutex<SampleHistory>>,
+    config: Option<&'a AudioConfigInfo>,
     rand: u32,
+    default_channel_volume: f32,
 
     none_audio_channel: AudioChannel,
 }
 
 impl<'a> AudioWrapper<'a> {
-    pub(crate) fn new(active_audio: Option<&'a mut ActiveAudio>, rand_source: u64) -> Self {
-        if let Some(active_audio) = active_audio {
+    pub(crate) fn new(shared_audio: Option<&'a SharedAudio>, rand_source: u64) -> Self {
+        if let Some(shared_audio) = shared_audio {
             Self {
-                sample_rate: active_audio.sample_rate,
-                channels: Some(active_audio.channels.lock().unwrap()),
+                sample_rate: shared_audio.sample_rate,
+                channels: Some(shared_audio.channels.lock().unwrap()),
+                master_peak: f32::from_bits(shared_audio.master_peak.load(Ordering::Relaxed)),
+                limiter: Some(&shared_audio.limiter),
+                paused: Some(&shared_audio.paused),
+                history: Some(&shared_audio.history),
+                config: Some(&shared_audio.config),
                 rand: simple_hash(rand_source as u32),
+                default_channel_volume: shared_audio.default_channel_volume,
 
                 none_audio_channel: AudioChannel::default(),
             }
@@ -53,7 +169,13 @@ impl<'a> AudioWrapper<'a> {
         Self {
             sample_rate: 0,
             channels: None,
+            master_peak: 0.0,
+            limiter: None,
+            paused: None,
+            history: None,
+            config: None,
             rand: 0,
+            default_channel_volume: DEFAULT_CHANNEL_VOLUME,
 
             none_audio_channel: AudioChannel::default(),
         }
@@ -68,9 +190,88 @@ impl<'a> AudioWrapper<'a> {
     pub fn is_active(&self) -> bool {
         self.channels.is_some()
     }
-    pub fn add_synth_channel(&mut self, sample: Box<[f32]>) -> AudioChannelId {
+    /// The configuration the audio device was opened with, or `None` if there's no audio.
+    #[inline]
+    pub fn output_config(&self) -> Option<&AudioConfigInfo> {
+        self.config
+    }
+    /// The loudest sample of the mixed output over roughly the last frame, from 0 upwards. See
+    /// [`AudioChannel::peak_level`].
+    #[inline]
+    pub fn master_peak(&self) -> f32 {
+        self.master_peak
+    }
+    /// The last `n` samples of the mixed output, oldest first, for drawing the waveform like an
+    /// oscilloscope. Returns fewer if there haven't been that many yet or `n` is more than the
+    /// roughly tenth of a second that's kept.
+    ///
+    /// Samples show up here once they're mixed, which is ahead of when they're heard by about
+    /// the device's buffer size; see [`AudioConfigInfo::buffer_size`].
+    pub fn recent_samples(&self, n: usize) -> Vec<f32> {
+        self.history
+            .map_or_else(Vec::new, |history| history.lock().unwrap().recent(n))
+    }
+    /// How the mixed output is kept from clipping. [`Limiter::Clamp`] by default.
+    #[inline]
+    pub fn limiter(&self) -> Limiter {
+        self.limiter.map_or(Limiter::default(), |limiter| {
+            Limiter::from_u8(limiter.load(Ordering::Relaxed))
+        })
+    }
+    /// Sets how the mixed output is kept from clipping once several loud channels add up to
+    /// more than the output can hold. See [`Limiter`].
+    pub fn set_limiter(&mut self, limiter: Limiter) {
+        if let Some(atomic) = self.limiter {
+            atomic.store(limiter as u8, Ordering::Relaxed);
+        }
+    }
+    /// Silences all audio until [`resume`](Self::resume), without stopping any channels. While
+    /// paused, channels don't advance, so sounds and sweeps pick up where they left off once
+    /// audio resumes. For muting the game or pausing its music separately from the game itself.
+    pub fn pause(&mut self) {
+        if let Some(paused) = self.paused {
+            paused.store(true, Ordering::Relaxed);
+        }
+    }
+    /// Undoes [`pause`](Self::pause).
+    pub fn resume(&mut self) {
+        if let Some(paused) = self.paused {
+            paused.store(false, Ordering::Relaxed);
+        }
+    }
+    #[inline]
+    pub fn is_paused(&self) -> bool {
+        self.paused
+            .is_some_and(|paused| paused.load(Ordering::Relaxed))
+    }
+    /// Adds a channel that plays `sample` as one period of its waveform.
+    ///
+    /// Passing an `Arc` lets several channels share the same sample without copying it.
+    pub fn add_synth_channel(&mut self, sample: impl Into<Arc<[f32]>>) -> AudioChannelId {
         if let Some(channels) = &mut self.channels {
-            channels.push(AudioChannel::synth(self.sample_rate, sample));
+            channels.push(AudioChannel {
+                channel_volume: self.default_channel_volume,
+                ..AudioChannel::synth(self.sample_rate, sample.into())
+            });
+            AudioChannelId(channels.len() as u32 - 1)
+        } else {
+            AudioChannelId::none()
+        }
+    }
+    /// Adds a channel that crossfades between two waveforms, playing `a` when `blend` is 0 and
+    /// `b` when it's 1. Both are played at the channel's pitch, one period each, so they can
+    /// have different lengths. See [`AudioChannel::set_blend`].
+    pub fn add_mixed_channel(
+        &mut self,
+        a: impl Into<Arc<[f32]>>,
+        b: impl Into<Arc<[f32]>>,
+        blend: f32,
+    ) -> AudioChannelId {
+        if let Some(channels) = &mut self.channels {
+            channels.push(AudioChannel {
+                channel_volume: self.default_channel_volume,
+                ..AudioChannel::mixed(self.sample_rate, a.into(), b.into(), blend)
+            });
             AudioChannelId(channels.len() as u32 - 1)
         } else {
             AudioChannelId::none()
@@ -79,12 +280,107 @@ impl<'a> AudioWrapper<'a> {
     pub fn add_noise_channel(&mut self) -> AudioChannelId {
         let rand = self.next_rand();
         if let Some(channels) = &mut self.channels {
-            channels.push(AudioChannel::noise(self.sample_rate, rand));
+            channels.push(AudioChannel {
+                channel_volume: self.default_channel_volume,
+                ..AudioChannel::noise(self.sample_rate, rand)
+            });
             AudioChannelId(channels.len() as u32 - 1)
         } else {
             AudioChannelId::none()
         }
     }
+    /// Plays `note` on a copy of the channel `template_id`, so that repeated calls overlap
+    /// instead of cutting each other off. The copy shares the template's sample data, volume and filter.
+    ///
+    /// Copies come from a pool: a copy that has finished playing is reused by later calls, so
+    /// the returned id is only valid until the copy stops. The template channel itself is never
+    /// touched.
+    pub fn play_sample(&mut self, template_id: AudioChannelId, note: i16) -> AudioChannelId {
+        let Some(channels) = &mut self.channels else {
+            return AudioChannelId::none();
+        };
+        let template = channels
+            .get(template_id.0 as usize)
+            .expect("invalid channel id");
+        let data = template.data.clone();
+        let channel_volume = template.channel_volume;
+        let lowpass_coefficient = template.lowpass_coefficient;
+        let sample_root = template.sample_root;
+
+        let index = Self::pooled_channel(channels, self.sample_rate);
+
+        let channel = &mut channels[index];
+        channel.data = data;
+        channel.channel_volume = channel_volume;
+        channel.lowpass_coefficient = lowpass_coefficient;
+        channel.sample_root = sample_root;
+        channel.play_note(note);
+
+        AudioChannelId(index as u32)
+    }
+    /// Plays a chord of `base_note` plus each of `intervals` (in semitones) using copies of
+    /// `template_id`, returning the ids of the copies in the same order as `intervals`.
+    ///
+    /// Each note goes through [`play_sample`](Self::play_sample), so the pool grows as needed and
+    /// every note always gets its own channel.
+    pub fn play_chord(
+        &mut self,
+        template_id: AudioChannelId,
+        base_note: i16,
+        intervals: &[i16],
+    ) -> Vec<AudioChannelId> {
+        intervals
+            .iter()
+            .map(|interval| self.play_sample(template_id, base_note + interval))
+            .collect()
+    }
+    /// Plays a beep at `hertz` that fades out over `seconds`, for when a sound is needed without
+    /// setting up a channel for it. Tones come from the same pool as
+    /// [`play_sample`](Self::play_sample), so the returned id is only valid until the tone ends.
+    pub fn play_tone(&mut self, hertz: f32, seconds: f32, waveform: Waveform) -> AudioChannelId {
+        let rand = self.next_rand();
+        let Some(channels) = &mut self.channels else {
+            return AudioChannelId::none();
+        };
+        let index = Self::pooled_channel(channels, self.sample_rate);
+
+        let channel = &mut channels[index];
+        let defaults = AudioChannel::default();
+        channel.data = waveform.channel_data(rand);
+        channel.channel_volume = self.default_channel_volume;
+        channel.lowpass_coefficient = defaults.lowpass_coefficient;
+        channel.sample_root = None;
+        channel.play_pitch(hertz);
+        channel.volume_sweep(0.0, seconds);
+
+        AudioChannelId(index as u32)
+    }
+    // Finds a pooled channel that has stopped playing, adding one if there are none
+    fn pooled_channel(channels: &mut Vec<AudioChannel>, sample_rate: u32) -> usize {
+        match channels
+            .iter()
+            .position(|channel| channel.pooled && !channel.is_playing())
+        {
+            Some(index) => index,
+            None => {
+                channels.push(AudioChannel {
+                    pooled: true,
+                    ..AudioChannel::with_sample_rate(sample_rate)
+                });
+                channels.len() - 1
+            }
+        }
+    }
+    /// Whether `id` refers to a channel, so that [`get_channel`](Self::get_channel) won't panic
+    /// on it. Always false without audio, where `get_channel` doesn't panic anyway.
+    ///
+    /// Ids from [`play_sample`](Self::play_sample) and [`play_tone`](Self::play_tone) stay valid
+    /// after their sound ends, but may have been reused for another sound by then.
+    pub fn is_valid(&self, id: AudioChannelId) -> bool {
+        self.channels
+            .as_ref()
+            .is_some_and(|channels| (id.0 as usize) < channels.len())
+    }
     pub fn get_channel(&mut self, id: AudioChannelId) -> &mut AudioChannel {
         if let Some(channels) = &mut self.channels {
             channels.get_mut(id.0 as usize).expect("invalid channel id")
@@ -94,32 +390,119 @@ impl<'a> AudioWrapper<'a> {
     }
 }
 
+/// Identifies an audio output device. Devices are matched by name.
+#[derive(PartialEq, Eq, Clone, Hash, Debug)]
+pub struct AudioDeviceId(String);
+
+#[derive(Clone, Debug)]
+pub struct AudioDeviceInfo {
+    pub name: String,
+    pub id: AudioDeviceId,
+}
+
+/// The output stream configuration chosen for the audio device.
+#[derive(Clone, Debug)]
+pub struct AudioConfigInfo {
+    pub sample_rate: u32,
+    pub channels: u16,
+    /// The format of samples sent to the device, like `"f32"` or `"i16"`.
+    pub sample_format: String,
+    /// The smallest and largest buffer sizes the device supports, in frames, if it reports them.
+    pub buffer_size: Option<(u32, u32)>,
+}
+
+/// Lists the output devices of the default audio host.
+pub fn output_devices() -> Vec<AudioDeviceInfo> {
+    let Ok(devices) = cpal::default_host().output_devices() else {
+        return Vec::new();
+    };
+    devices
+        .filter_map(|device| device.name().ok())
+        .map(|name| AudioDeviceInfo {
+            id: AudioDeviceId(name.clone()),
+            name,
+        })
+        .collect()
+}
+
 pub(crate) struct ActiveAudio {
     sample_rate: u32,
     channels: Arc<Mutex<Vec<AudioChannel>>>,
+    // The mixed output's peak level as f32 bits, written by the stream
+    master_peak: Arc<AtomicU32>,
+    limiter: Arc<AtomicU8>,
+    paused: Arc<AtomicBool>,
+    history: Arc<Mutex<SampleHistory>>,
+    config: Arc<AudioConfigInfo>,
+    default_channel_volume: f32,
     _stream: Stream,
 }
 
+/// The channels of an [`ActiveAudio`] without its output stream, which can be sent to other
+/// threads.
+#[derive(Clone)]
+pub(crate) struct SharedAudio {
+    sample_rate: u32,
+    channels: Arc<Mutex<Vec<AudioChannel>>>,
+    master_peak: Arc<AtomicU32>,
+    limiter: Arc<AtomicU8>,
+    paused: Arc<AtomicBool>,
+    history: Arc<Mutex<SampleHistory>>,
+    config: Arc<AudioConfigInfo>,
+    default_channel_volume: f32,
+}
+
 impl ActiveAudio {
+    pub fn shared(&self) -> SharedAudio {
+        SharedAudio {
+            sample_rate: self.sample_rate,
+            channels: self.channels.clone(),
+            master_peak: self.master_peak.clone(),
+            limiter: self.limiter.clone(),
+            paused: self.paused.clone(),
+            history: self.history.clone(),
+            config: self.config.clone(),
+            default_channel_volume: self.default_channel_volume,
+        }
+    }
+
     fn get_output_stream<S: SizedSample + cpal::FromSample<f32>>(
         device: Device,
         config: &StreamConfig,
         mutex: Arc<Mutex<Vec<AudioChannel>>>,
+        master_peak: Arc<AtomicU32>,
+        limiter: Arc<AtomicU8>,
+        paused: Arc<AtomicBool>,
+        history: Arc<Mutex<SampleHistory>>,
     ) -> Stream {
         let mut frame = 0;
         let num_channels = config.channels;
+        let sample_rate = config.sample_rate.0 as f32;
+        let mut master_peak_meter = PeakMeter::default();
 
         device
             .build_output_stream(
                 config,
                 move |data: &mut [S], _callback_info: &OutputCallbackInfo| {
+                    if paused.load(Ordering::Relaxed) {
+                        data.fill(S::EQUILIBRIUM);
+                        master_peak.store(0f32.to_bits(), Ordering::Relaxed);
+                        return;
+                    }
+
                     let mut channels = mutex.lock().unwrap();
+                    let mut history = history.lock().unwrap();
+                    let limiter = Limiter::from_u8(limiter.load(Ordering::Relaxed));
 
                     for x in data.chunks_exact_mut(num_channels as usize) {
-                        let sample = Self::next_sample(&mut channels, frame);
+                        let sample = Self::next_sample(&mut channels, frame, limiter);
                         x.fill(sample.to_sample());
+                        history.push(sample);
                         frame += 1;
+
+                        master_peak_meter.add(sample, sample_rate);
                     }
+                    master_peak.store(master_peak_meter.level.to_bits(), Ordering::Relaxed);
                 },
                 |err| {
                     eprintln!("{err:?}");
@@ -129,17 +512,38 @@ impl ActiveAudio {
             .unwrap()
     }
 
-    fn next_sample(channels: &mut [AudioChannel], frame: u64) -> f32 {
+    fn next_sample(channels: &mut [AudioChannel], frame: u64, limiter: Limiter) -> f32 {
         let mut tot: f32 = 0.0;
         for channel in channels.iter_mut() {
-            tot += channel.next_sample(frame);
+            let sample = channel.next_sample(frame);
+            channel.peak_meter.add(sample, channel.sample_rate);
+            tot += sample;
         }
-        tot
+        limiter.apply(tot)
     }
 
-    pub fn new() -> Result<Option<Self>, Box<dyn Error>> {
+    /// Opens `device_id`, or the default output device if it's `None` or no longer available.
+    /// Channels added later start at `default_channel_volume`.
+    pub fn new(
+        device_id: Option<&AudioDeviceId>,
+        default_channel_volume: f32,
+    ) -> Result<Option<Self>, Box<dyn Error>> {
         let host = cpal::default_host();
-        let Some(device) = host.default_output_device() else { return Ok(None); };
+        let device = device_id.and_then(|id| {
+            host.output_devices()
+                .ok()?
+                .find(|device| device.name().is_ok_and(|name| name == id.0))
+        });
+        let Some(device) = device.or_else(|| host.default_output_device()) else {
+            return Ok(None);
+        };
+        Self::with_device(device, default_channel_volume)
+    }
+
+    fn with_device(
+        device: Device,
+        default_channel_volume: f32,
+    ) -> Result<Option<Self>, Box<dyn Error>> {
         let config_range = device
             .supported_output_configs()?
             .min_by_key(|config| {
@@ -171,45 +575,118 @@ impl ActiveAudio {
 
                 sample_rate_score + buffer_size_score + audio_format_score + channel_score
             })
-            .ok_or_else(|| crate::StrError::new(&"no supported configs available"))?;
+            .ok_or_else(|| crate::StrError::new("no supported configs available"))?;
         let sample_rate = config_range
             .min_sample_rate()
             .max(SampleRate(MIN_SAMPLE_RATE));
         let config = config_range.with_sample_rate(sample_rate);
+        let config_info = AudioConfigInfo {
+            sample_rate: sample_rate.0,
+            channels: config.channels(),
+            sample_format: config.sample_format().to_string(),
+            buffer_size: match *config.buffer_size() {
+                SupportedBufferSize::Range { min, max } => Some((min, max)),
+                SupportedBufferSize::Unknown => None,
+            },
+        };
 
         let mutex = Arc::new(Mutex::new(Vec::new()));
+        let master_peak = Arc::new(AtomicU32::new(0));
+        let limiter = Arc::new(AtomicU8::new(Limiter::default() as u8));
+        let paused = Arc::new(AtomicBool::new(false));
+        let history = Arc::new(Mutex::new(SampleHistory::new(sample_rate.0)));
 
         let stream = match config.sample_format() {
-            SampleFormat::I8 => {
-                Self::get_output_stream::<i8>(device, &config.into(), mutex.clone())
-            }
-            SampleFormat::I16 => {
-                Self::get_output_stream::<i16>(device, &config.into(), mutex.clone())
-            }
-            SampleFormat::I32 => {
-                Self::get_output_stream::<i32>(device, &config.into(), mutex.clone())
-            }
-            SampleFormat::I64 => {
-                Self::get_output_stream::<i64>(device, &config.into(), mutex.clone())
-            }
-            SampleFormat::U8 => {
-                Self::get_output_stream::<u8>(device, &config.into(), mutex.clone())
-            }
-            SampleFormat::U16 => {
-                Self::get_output_stream::<u16>(device, &config.into(), mutex.clone())
-            }
-            SampleFormat::U32 => {
-                Self::get_output_stream::<u32>(device, &config.into(), mutex.clone())
-            }
-            SampleFormat::U64 => {
-                Self::get_output_stream::<u64>(device, &config.into(), mutex.clone())
-            }
-            SampleFormat::F32 => {
-                Self::get_output_stream::<f32>(device, &config.into(), mutex.clone())
-            }
-            SampleFormat::F64 => {
-                Self::get_output_stream::<f64>(device, &config.into(), mutex.clone())
-            }
+            SampleFormat::I8 => Self::get_output_stream::<i8>(
+                device,
+                &config.into(),
+                mutex.clone(),
+                master_peak.clone(),
+                limiter.clone(),
+                paused.clone(),
+                history.clone(),
+            ),
+            SampleFormat::I16 => Self::get_output_stream::<i16>(
+                device,
+                &config.into(),
+                mutex.clone(),
+                master_peak.clone(),
+                limiter.clone(),
+                paused.clone(),
+                history.clone(),
+            ),
+            SampleFormat::I32 => Self::get_output_stream::<i32>(
+                device,
+                &config.into(),
+                mutex.clone(),
+                master_peak.clone(),
+                limiter.clone(),
+                paused.clone(),
+                history.clone(),
+            ),
+            SampleFormat::I64 => Self::get_output_stream::<i64>(
+                device,
+                &config.into(),
+                mutex.clone(),
+                master_peak.clone(),
+                limiter.clone(),
+                paused.clone(),
+                history.clone(),
+            ),
+            SampleFormat::U8 => Self::get_output_stream::<u8>(
+                device,
+                &config.into(),
+                mutex.clone(),
+                master_peak.clone(),
+                limiter.clone(),
+                paused.clone(),
+                history.clone(),
+            ),
+            SampleFormat::U16 => Self::get_output_stream::<u16>(
+                device,
+                &config.into(),
+                mutex.clone(),
+                master_peak.clone(),
+                limiter.clone(),
+                paused.clone(),
+                history.clone(),
+            ),
+            SampleFormat::U32 => Self::get_output_stream::<u32>(
+                device,
+                &config.into(),
+                mutex.clone(),
+                master_peak.clone(),
+                limiter.clone(),
+                paused.clone(),
+                history.clone(),
+            ),
+            SampleFormat::U64 => Self::get_output_stream::<u64>(
+                device,
+                &config.into(),
+                mutex.clone(),
+                master_peak.clone(),
+                limiter.clone(),
+                paused.clone(),
+                history.clone(),
+            ),
+            SampleFormat::F32 => Self::get_output_stream::<f32>(
+                device,
+                &config.into(),
+                mutex.clone(),
+                master_peak.clone(),
+                limiter.clone(),
+                paused.clone(),
+                history.clone(),
+            ),
+            SampleFormat::F64 => Self::get_output_stream::<f64>(
+                device,
+                &config.into(),
+                mutex.clone(),
+                master_peak.clone(),
+                limiter.clone(),
+                paused.clone(),
+                history.clone(),
+            ),
             _ => unreachable!(),
         };
 
@@ -218,6 +695,12 @@ impl ActiveAudio {
         let obj = Self {
             sample_rate: sample_rate.0,
             channels: mutex.clone(),
+            master_peak,
+            limiter,
+            paused,
+            history,
+            config: Arc::new(config_info),
+            default_channel_volume,
             _stream: stream,
         };
 
@@ -225,6 +708,61 @@ impl ActiveAudio {
     }
 }
 
+/// Mixes audio into memory instead of playing it on a device, for testing sound code without
+/// audio hardware or bouncing a track to a file.
+pub struct OfflineAudio {
+    shared: SharedAudio,
+    frame: u64,
+}
+
+impl OfflineAudio {
+    pub fn new(sample_rate: u32) -> Self {
+        Self {
+            shared: SharedAudio {
+                sample_rate,
+                channels: Arc::new(Mutex::new(Vec::new())),
+                master_peak: Arc::new(AtomicU32::new(0)),
+                limiter: Arc::new(AtomicU8::new(Limiter::default() as u8)),
+                paused: Arc::new(AtomicBool::new(false)),
+                history: Arc::new(Mutex::new(SampleHistory::new(sample_rate))),
+                config: Arc::new(AudioConfigInfo {
+                    sample_rate,
+                    channels: 1,
+                    sample_format: String::from("f32"),
+                    buffer_size: None,
+                }),
+                default_channel_volume: DEFAULT_CHANNEL_VOLUME,
+            },
+            frame: 0,
+        }
+    }
+
+    /// An [`AudioWrapper`] for adding and playing channels, like the one passed to the frame
+    /// callback.
+    pub fn wrapper(&mut self) -> AudioWrapper<'_> {
+        AudioWrapper::new(Some(&self.shared), self.frame)
+    }
+
+    /// Mixes the next `frames` samples, exactly as they would be sent to an audio device. Output
+    /// is mono, at the sample rate given to [`new`](Self::new).
+    pub fn render_offline(&mut self, frames: usize) -> Vec<f32> {
+        if self.shared.paused.load(Ordering::Relaxed) {
+            return vec![0.0; frames];
+        }
+        let mut channels = self.shared.channels.lock().unwrap();
+        let mut history = self.shared.history.lock().unwrap();
+        let limiter = Limiter::from_u8(self.shared.limiter.load(Ordering::Relaxed));
+        (0..frames)
+            .map(|_| {
+                let sample = ActiveAudio::next_sample(&mut channels, self.frame, limiter);
+                history.push(sample);
+                self.frame += 1;
+                sample
+            })
+            .collect()
+    }
+}
+
 #[derive(Debug)]
 pub struct AudioChannel {
     sample_rate: f32,
@@ -237,20 +775,42 @@ pub struct AudioChannel {
     pitch_sweep: f32,
 
     osc_timer: f32,
+    // The frequency the sample plays at when played at its recorded speed, if it's a recording
+    sample_root: Option<f32>,
+
+    // One-pole low-pass filter; a coefficient of 1.0 leaves samples unchanged
+    lowpass_coefficient: f32,
+    lowpass_value: f32,
+
+    // Vibrato depth in cents, and its rate and phase in cycles per output sample and cycles
+    vibrato_depth: f32,
+    vibrato_rate: f32,
+    vibrato_phase: f32,
 
     stopped: bool,
+    // Created by `AudioWrapper::play_sample` and free for reuse once stopped
+    pooled: bool,
+
+    peak_meter: PeakMeter,
 
     data: AudioChannelData,
 }
 
 impl AudioChannel {
-    fn synth(sample_rate: u32, sample: Box<[f32]>) -> Self {
+    fn synth(sample_rate: u32, sample: Arc<[f32]>) -> Self {
         Self {
             data: AudioChannelData::Synth { sample },
             ..Self::with_sample_rate(sample_rate)
         }
     }
 
+    fn mixed(sample_rate: u32, a: Arc<[f32]>, b: Arc<[f32]>, blend: f32) -> Self {
+        Self {
+            data: AudioChannelData::Mixed { a, b, blend },
+            ..Self::with_sample_rate(sample_rate)
+        }
+    }
+
     fn noise(sample_rate: u32, lfsr: u32) -> Self {
         Self {
             data: AudioChannelData::Noise {
@@ -277,22 +837,23 @@ impl AudioChannel {
             return 0.0;
         }
 
-        let next_osc_timer = self.osc_timer + self.pitch;
+        let pitch = if self.vibrato_depth != 0.0 {
+            let cents = self.vibrato_depth * (self.vibrato_phase * std::f32::consts::TAU).sin();
+            self.vibrato_phase = (self.vibrato_phase + self.vibrato_rate) % 1.0;
+            self.pitch * 2f32.powf(cents / 1200.0)
+        } else {
+            self.pitch
+        };
+        let next_osc_timer = self.osc_timer + pitch;
 
         let sample = match &mut self.data {
             AudioChannelData::Synth { sample } => {
-                let this_sample = (self.osc_timer * sample.len() as f32) as usize;
-                let next_sample = (next_osc_timer * sample.len() as f32) as usize;
-
-                if this_sample == next_sample {
-                    sample[this_sample]
-                } else {
-                    let middle_osc_timer = next_sample as f32 / sample.len() as f32;
-                    let this_sample_portion = (middle_osc_timer - self.osc_timer) / self.pitch;
-                    // dbg!(this_sample_portion);
-                    sample[this_sample] * this_sample_portion
-                        + sample[next_sample % sample.len()] * (1.0 - this_sample_portion)
-                }
+                Self::sample_waveform(sample, self.osc_timer, next_osc_timer, pitch)
+            }
+            AudioChannelData::Mixed { a, b, blend } => {
+                let a = Self::sample_waveform(a, self.osc_timer, next_osc_timer, pitch);
+                let b = Self::sample_waveform(b, self.osc_timer, next_osc_timer, pitch);
+                a + (b - a) * *blend
             }
             AudioChannelData::Noise { lfsr, last_value } => {
                 let this_sample = self.osc_timer as usize;
@@ -305,13 +866,14 @@ impl AudioChannel {
                     *last_value = if (*lfsr & 1) == 0 { 1.0 } else { -1.0 };
 
                     let middle_osc_timer = next_sample as f32;
-                    let this_sample_portion = (middle_osc_timer - self.osc_timer) / self.pitch;
+                    let this_sample_portion = (middle_osc_timer - self.osc_timer) / pitch;
                     old_value * this_sample_portion + *last_value * (1.0 - this_sample_portion)
                 }
             }
             _ => unreachable!(),
         };
-        let sample = sample as f32 * self.note_volume * self.channel_volume;
+        self.lowpass_value += (sample - self.lowpass_value) * self.lowpass_coefficient;
+        let sample = self.lowpass_value * self.note_volume * self.channel_volume;
 
         self.osc_timer = next_osc_timer % 1.0;
         self.pitch += self.pitch_sweep;
@@ -320,6 +882,22 @@ impl AudioChannel {
         sample
     }
 
+    // Samples one period of `sample` at `osc_timer`, which goes from 0 to 1 over the period
+    fn sample_waveform(sample: &[f32], osc_timer: f32, next_osc_timer: f32, pitch: f32) -> f32 {
+        let this_sample = (osc_timer * sample.len() as f32) as usize;
+        let next_sample = (next_osc_timer * sample.len() as f32) as usize;
+
+        if this_sample == next_sample {
+            sample[this_sample]
+        } else {
+            let middle_osc_timer = next_sample as f32 / sample.len() as f32;
+            let this_sample_portion = (middle_osc_timer - osc_timer) / pitch;
+            // dbg!(this_sample_portion);
+            sample[this_sample] * this_sample_portion
+                + sample[next_sample % sample.len()] * (1.0 - this_sample_portion)
+        }
+    }
+
     fn stop_notes(&mut self) {
         self.osc_timer = 0.0;
         self.note_volume = 1.0;
@@ -333,14 +911,74 @@ impl AudioChannel {
         self.stopped = true;
     }
 
+    #[inline]
+    pub fn is_playing(&self) -> bool {
+        !self.stopped && !matches!(self.data, AudioChannelData::None)
+    }
+    #[inline]
+    pub fn is_stopped(&self) -> bool {
+        !self.is_playing()
+    }
+
+    /// The loudest sample this channel produced over roughly the last frame, after volume is
+    /// applied, for driving visualizers.
+    ///
+    /// Levels are measured as samples are sent to the audio device, which runs a little ahead of
+    /// what's audible, so they lag behind the game by up to one audio buffer.
+    #[inline]
+    pub fn peak_level(&self) -> f32 {
+        self.peak_meter.level
+    }
+
     pub fn set_channel_volume(&mut self, volume: f32) {
         self.channel_volume = volume;
     }
 
+    /// Sets how far a channel made with [`AudioWrapper::add_mixed_channel`] is between its two
+    /// waveforms, from 0 to 1. Does nothing for other channels.
+    pub fn set_blend(&mut self, blend: f32) {
+        if let AudioChannelData::Mixed { blend: current, .. } = &mut self.data {
+            *current = blend;
+        }
+    }
+
+    /// Treats the channel's sample as a recording of a sound at `root_hz` instead of a single
+    /// period of a waveform, so notes and pitches transpose it: playing the note an octave above
+    /// `root_hz` plays the recording at double speed. The recording is assumed to be at the
+    /// output sample rate.
+    ///
+    /// Only affects synth channels, and takes effect the next time a note or pitch is set.
+    pub fn set_sample_root(&mut self, root_hz: f32) {
+        self.sample_root = Some(root_hz);
+    }
+
+    /// Filters the channel's output with a one-pole low-pass filter, softening frequencies above
+    /// `cutoff_hz`. A cutoff of 0 disables the filter.
+    pub fn set_lowpass(&mut self, cutoff_hz: f32) {
+        self.lowpass_coefficient = if cutoff_hz > 0.0 {
+            1.0 - (-std::f32::consts::TAU * cutoff_hz / self.sample_rate).exp()
+        } else {
+            1.0
+        };
+    }
+
+    /// Wobbles the pitch up and down by `depth_cents` hundredths of a semitone, `rate_hz` times
+    /// a second, on top of whatever note or pitch is playing. A depth of 0 turns vibrato off.
+    pub fn set_vibrato(&mut self, depth_cents: f32, rate_hz: f32) {
+        self.vibrato_depth = depth_cents;
+        self.vibrato_rate = rate_hz / self.sample_rate;
+    }
+
     // Note-playing functions
 
-    fn get_pitch(note: i16) -> f32 {
-        440.0 * 2f32.powf(note as f32 * (1.0 / 12.0))
+    // How far through the sample to advance each output sample to play at `hertz`
+    fn hertz_to_pitch(&self, hertz: f32) -> f32 {
+        match (self.sample_root, &self.data) {
+            (Some(root_hz), AudioChannelData::Synth { sample }) => {
+                hertz / root_hz / sample.len() as f32
+            }
+            _ => hertz / self.sample_rate,
+        }
     }
 
     pub fn play(&mut self) {
@@ -350,22 +988,27 @@ impl AudioChannel {
     }
     pub fn play_note(&mut self, note: i16) {
         self.stop_notes();
-        self.pitch = Self::get_pitch(note) / self.sample_rate;
+        self.pitch = self.hertz_to_pitch(note_to_hz(note));
         self.stopped = false;
     }
+    /// Plays the note `degree` steps up `scale` from `root`, so that generated melodies stay in
+    /// key. See [`Scale::note`].
+    pub fn play_scale_degree(&mut self, root: i16, scale: &Scale, degree: i16) {
+        self.play_note(scale.note(root, degree));
+    }
     pub fn play_pitch(&mut self, hertz: f32) {
         self.stop_notes();
-        self.pitch = hertz / self.sample_rate;
+        self.pitch = self.hertz_to_pitch(hertz);
         self.stopped = false;
     }
 
     // "Modifier" functions
 
     pub fn set_note(&mut self, note: i16) {
-        self.pitch = Self::get_pitch(note) / self.sample_rate;
+        self.pitch = self.hertz_to_pitch(note_to_hz(note));
     }
     pub fn set_pitch(&mut self, hertz: f32) {
-        self.pitch = hertz / self.sample_rate;
+        self.pitch = self.hertz_to_pitch(hertz);
     }
     pub fn set_volume(&mut self, volume: f32) {
         self.note_volume = volume;
@@ -375,7 +1018,7 @@ impl AudioChannel {
         self.volume_sweep = (end_volume - self.note_volume) / (seconds * self.sample_rate)
     }
     pub fn pitch_sweep(&mut self, end_note: i16, seconds: f32) {
-        let end_pitch = Self::get_pitch(end_note) / self.sample_rate;
+        let end_pitch = self.hertz_to_pitch(note_to_hz(end_note));
         self.pitch_sweep = (end_pitch - self.pitch) / (seconds * self.sample_rate)
     }
 }
@@ -385,7 +1028,7 @@ impl Default for AudioChannel {
         Self {
             sample_rate: 0.0,
 
-            channel_volume: 0.05,
+            channel_volume: DEFAULT_CHANNEL_VOLUME,
 
             note_volume: 1.0,
             volume_sweep: 0.0,
@@ -393,17 +1036,576 @@ impl Default for AudioChannel {
             pitch_sweep: 0.0,
 
             osc_timer: 0.0,
+            sample_root: None,
+
+            lowpass_coefficient: 1.0,
+            lowpass_value: 0.0,
+
+            vibrato_depth: 0.0,
+            vibrato_rate: 0.0,
+            vibrato_phase: 0.0,
 
             stopped: true,
+            pooled: false,
+
+            peak_meter: PeakMeter::default(),
 
             data: AudioChannelData::None,
         }
     }
 }
 
-#[derive(Debug)]
+// Tracks the loudest sample over windows of about one frame at 60 FPS
+#[derive(Debug, Clone, Copy, Default)]
+struct PeakMeter {
+    window_peak: f32,
+    window_samples: u32,
+    level: f32,
+}
+
+impl PeakMeter {
+    fn add(&mut self, sample: f32, sample_rate: f32) {
+        self.window_peak = self.window_peak.max(sample.abs());
+        self.window_samples += 1;
+        if self.window_samples as f32 >= sample_rate / 60.0 {
+            self.level = self.window_peak;
+            self.window_peak = 0.0;
+            self.window_samples = 0;
+        }
+    }
+}
+
+// The most recent samples of the mixed output, for `AudioWrapper::recent_samples`
+struct SampleHistory {
+    samples: Vec<f32>,
+    // Where the next sample goes, which is also the oldest one once the buffer is full
+    next: usize,
+    full: bool,
+}
+
+impl SampleHistory {
+    // A tenth of a second, which is a few frames even at low frame rates
+    fn new(sample_rate: u32) -> Self {
+        Self {
+            samples: vec![0.0; (sample_rate / 10).max(1) as usize],
+            next: 0,
+            full: false,
+        }
+    }
+
+    #[inline]
+    fn push(&mut self, sample: f32) {
+        self.samples[self.next] = sample;
+        self.next += 1;
+        if self.next == self.samples.len() {
+            self.next = 0;
+            self.full = true;
+        }
+    }
+
+    fn recent(&self, n: usize) -> Vec<f32> {
+        let len = if self.full {
+            self.samples.len()
+        } else {
+            self.next
+        };
+        let n = n.min(len);
+        let start = (self.next + self.samples.len() - n) % self.samples.len();
+        if start + n <= self.samples.len() {
+            self.samples[start..start + n].to_vec()
+        } else {
+            [&self.samples[start..], &self.samples[..self.next]].concat()
+        }
+    }
+}
+
+/// How the sum of all channels is kept inside the -1 to 1 range the output can hold. Without a
+/// limiter, several loud channels playing at once clip harshly on the device.
+#[derive(PartialEq, Eq, Clone, Copy, Hash, Debug, Default)]
+pub enum Limiter {
+    /// Passes the mix through as is.
+    None,
+    /// Cuts off anything outside of -1 to 1. Quiet mixes are unchanged.
+    #[default]
+    Clamp,
+    /// Squashes the mix smoothly towards -1 to 1 with `tanh`, which distorts less than
+    /// clamping when the mix is too loud but also makes everything a bit quieter.
+    SoftClip,
+}
+
+impl Limiter {
+    fn from_u8(value: u8) -> Self {
+        match value {
+            0 => Self::None,
+            1 => Self::Clamp,
+            _ => Self::SoftClip,
+        }
+    }
+
+    #[inline]
+    fn apply(self, sample: f32) -> f32 {
+        match self {
+            Self::None => sample,
+            Self::Clamp => sample.clamp(-1.0, 1.0),
+            Self::SoftClip => sample.tanh(),
+        }
+    }
+}
+
+/// Basic waveforms for [`AudioWrapper::play_tone`].
+#[derive(PartialEq, Eq, Clone, Copy, Hash, Debug)]
+pub enum Waveform {
+    Sine,
+    Square,
+    Triangle,
+    Sawtooth,
+    Noise,
+}
+
+impl Waveform {
+    const PERIOD_SAMPLES: usize = 64;
+
+    /// One period of the waveform, going from -1 to 1. Returns `None` for noise, which doesn't
+    /// repeat.
+    pub fn period(self) -> Option<Vec<f32>> {
+        let f: fn(f32) -> f32 = match self {
+            Self::Sine => |t| (t * std::f32::consts::TAU).sin(),
+            Self::Square => |t| if t < 0.5 { 1.0 } else { -1.0 },
+            Self::Triangle => |t| 1.0 - 4.0 * (t - 0.5).abs(),
+            Self::Sawtooth => |t| 2.0 * t - 1.0,
+            Self::Noise => return None,
+        };
+        Some(
+            (0..Self::PERIOD_SAMPLES)
+                .map(|i| f(i as f32 / Self::PERIOD_SAMPLES as f32))
+                .collect(),
+        )
+    }
+
+    fn channel_data(self, rand: u32) -> AudioChannelData {
+        match self.period() {
+            Some(period) => AudioChannelData::Synth {
+                sample: period.into(),
+            },
+            None => AudioChannelData::Noise {
+                lfsr: rand,
+                last_value: 0.0,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
 pub enum AudioChannelData {
-    Synth { sample: Box<[f32]> },
-    Noise { lfsr: u32, last_value: f32 },
+    Synth {
+        sample: Arc<[f32]>,
+    },
+    Mixed {
+        a: Arc<[f32]>,
+        b: Arc<[f32]>,
+        blend: f32,
+    },
+    Noise {
+        lfsr: u32,
+        last_value: f32,
+    },
     None,
 }
+
+/// Something a [`Sequencer`] does to a channel.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum SequencerEvent {
+    /// [`AudioChannel::play_note`].
+    Note(i16),
+    /// [`AudioChannel::play_pitch`].
+    Pitch(f32),
+    /// [`AudioChannel::set_volume`].
+    Volume(f32),
+    /// [`AudioChannel::volume_sweep`].
+    VolumeSweep { end_volume: f32, seconds: f32 },
+    /// [`AudioChannel::stop`].
+    Stop,
+}
+
+impl SequencerEvent {
+    fn apply(self, channel: &mut AudioChannel) {
+        match self {
+            Self::Note(note) => channel.play_note(note),
+            Self::Pitch(hertz) => channel.play_pitch(hertz),
+            Self::Volume(volume) => channel.set_volume(volume),
+            Self::VolumeSweep {
+                end_volume,
+                seconds,
+            } => channel.volume_sweep(end_volume, seconds),
+            Self::Stop => channel.stop(),
+        }
+    }
+}
+
+/// Plays a pattern of timed events on audio channels, for music and jingles. Each event happens
+/// a number of frames after the pattern starts; call [`update`](Self::update) every frame to
+/// fire the ones that are due.
+#[derive(Debug, Clone)]
+pub struct Sequencer {
+    // Sorted by frame
+    events: Vec<(u64, AudioChannelId, SequencerEvent)>,
+    loop_frames: Option<u64>,
+
+    start_frame: Option<u64>,
+    // How many times the pattern has looped, and the next event to fire in this pass
+    pass: u64,
+    next_event: usize,
+}
+
+impl Sequencer {
+    /// A sequencer for `events`, each given as the frame it happens on, counted from the start
+    /// of the pattern, the channel it affects and what it does. Events on the same frame happen
+    /// in the order they're listed.
+    pub fn new(mut events: Vec<(u64, AudioChannelId, SequencerEvent)>) -> Self {
+        events.sort_by_key(|&(frame, ..)| frame);
+        Self {
+            events,
+            loop_frames: None,
+
+            start_frame: None,
+            pass: 0,
+            next_event: 0,
+        }
+    }
+
+    /// Repeats the pattern every `frames` frames instead of playing it once.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `frames` is 0 or any event is on frame `frames` or later, since it would never
+    /// play.
+    pub fn looping(mut self, frames: u64) -> Self {
+        assert!(
+            frames > 0,
+            "a looping pattern has to be at least a frame long"
+        );
+        assert!(
+            self.events.iter().all(|&(frame, ..)| frame < frames),
+            "every event has to happen before the pattern loops"
+        );
+        self.loop_frames = Some(frames);
+        self
+    }
+
+    /// Starts the pattern over, with its first frame being `current_frame`.
+    pub fn restart(&mut self, current_frame: u64) {
+        self.start_frame = Some(current_frame);
+        self.pass = 0;
+        self.next_event = 0;
+    }
+
+    /// Fires every event that's due by `current_frame`, usually [`Context::current_frame`]. The
+    /// pattern starts on the first frame this is called, unless it was started with
+    /// [`restart`](Self::restart). Events from skipped frames are fired late rather than
+    /// dropped.
+    ///
+    /// [`Context::current_frame`]: crate::Context::current_frame
+    pub fn update(&mut self, current_frame: u64, audio: &mut AudioWrapper) {
+        let start_frame = *self.start_frame.get_or_insert(current_frame);
+        let elapsed = current_frame.saturating_sub(start_frame);
+        loop {
+            let pass_start = self.pass * self.loop_frames.unwrap_or(0);
+            while let Some(&(frame, channel, event)) = self.events.get(self.next_event) {
+                if pass_start + frame > elapsed {
+                    return;
+                }
+                event.apply(audio.get_channel(channel));
+                self.next_event += 1;
+            }
+            match self.loop_frames {
+                Some(loop_frames) if pass_start + loop_frames <= elapsed => {
+                    self.pass += 1;
+                    self.next_event = 0;
+                }
+                _ => return,
+            }
+        }
+    }
+
+    /// Whether every event has been fired. Looping patterns never finish.
+    pub fn is_finished(&self) -> bool {
+        self.loop_frames.is_none() && self.next_event == self.events.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A channel that plays one sample of `sample` per output sample, at full volume. Powers of
+    // two keep the oscillator exact
+    fn stepping_channel(sample: Vec<f32>) -> AudioChannel {
+        let len = sample.len() as u32;
+        let mut channel = AudioChannel::synth(len, sample.into());
+        channel.set_channel_volume(1.0);
+        channel.play_pitch(1.0);
+        channel
+    }
+
+    fn impulse() -> Vec<f32> {
+        let mut sample = vec![0.0; 1024];
+        sample[0] = 1.0;
+        sample
+    }
+
+    #[test]
+    fn lowpass_decays_an_impulse() {
+        let mut channel = stepping_channel(impulse());
+        channel.set_lowpass(100.0);
+        let coefficient = 1.0 - (-std::f32::consts::TAU * 100.0 / 1024.0).exp();
+
+        let response: Vec<f32> = (0..8).map(|frame| channel.next_sample(frame)).collect();
+        let mut expected = coefficient;
+        for sample in response {
+            assert!((sample - expected).abs() < 1e-6, "{sample} != {expected}");
+            expected *= 1.0 - coefficient;
+        }
+    }
+
+    #[test]
+    fn lowpass_of_zero_passes_samples_through() {
+        let mut channel = stepping_channel(impulse());
+        channel.set_lowpass(100.0);
+        channel.set_lowpass(0.0);
+        assert_eq!(channel.next_sample(0), 1.0);
+        assert_eq!(channel.next_sample(1), 0.0);
+    }
+
+    #[test]
+    fn mixed_channel_blends_between_waveforms() {
+        let mut channel = AudioChannel::mixed(4, vec![1.0; 4].into(), vec![0.0; 4].into(), 0.5);
+        channel.set_channel_volume(1.0);
+        channel.play_pitch(1.0);
+        assert_eq!(channel.next_sample(0), 0.5);
+
+        channel.set_blend(0.25);
+        assert_eq!(channel.next_sample(1), 0.75);
+        channel.set_blend(1.0);
+        assert_eq!(channel.next_sample(2), 0.0);
+    }
+
+    #[test]
+    fn sample_root_plays_an_octave_up_at_double_speed() {
+        let ramp: Vec<f32> = (0..8).map(|i| i as f32).collect();
+        let mut channel = stepping_channel(ramp);
+        channel.set_sample_root(440.0);
+
+        channel.play_pitch(440.0);
+        let root_pitch = channel.pitch;
+        let at_root: Vec<f32> = (0..4).map(|frame| channel.next_sample(frame)).collect();
+        assert_eq!(at_root, [0.0, 1.0, 2.0, 3.0]);
+
+        channel.play_pitch(880.0);
+        assert_eq!(channel.pitch, root_pitch * 2.0);
+        let octave_up: Vec<f32> = (0..4).map(|frame| channel.next_sample(frame)).collect();
+        assert_eq!(octave_up, [0.0, 2.0, 4.0, 6.0]);
+    }
+
+    fn mix(level: f32, count: usize, limiter: Limiter) -> f32 {
+        let mut channels: Vec<_> = (0..count)
+            .map(|_| stepping_channel(vec![level; 4]))
+            .collect();
+        ActiveAudio::next_sample(&mut channels, 0, limiter)
+    }
+
+    #[test]
+    fn limiters_keep_a_loud_mix_in_range() {
+        assert_eq!(mix(1.0, 4, Limiter::None), 4.0);
+        assert_eq!(mix(1.0, 4, Limiter::Clamp), 1.0);
+        assert_eq!(mix(-1.0, 4, Limiter::Clamp), -1.0);
+        let soft = mix(1.0, 4, Limiter::SoftClip);
+        assert!(soft > 0.99 && soft < 1.0, "{soft}");
+    }
+
+    #[test]
+    fn clamping_leaves_a_quiet_mix_alone() {
+        assert_eq!(mix(0.25, 3, Limiter::Clamp), 0.75);
+    }
+
+    #[test]
+    fn limiters_survive_being_stored_as_u8() {
+        for limiter in [Limiter::None, Limiter::Clamp, Limiter::SoftClip] {
+            assert_eq!(Limiter::from_u8(limiter as u8), limiter);
+        }
+    }
+
+    // How far the oscillator moves on each of the next `count` samples
+    fn oscillator_steps(channel: &mut AudioChannel, count: u64) -> Vec<f32> {
+        (0..count)
+            .map(|frame| {
+                let before = channel.osc_timer;
+                channel.next_sample(frame);
+                (channel.osc_timer - before).rem_euclid(1.0)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn vibrato_swings_evenly_around_the_base_pitch() {
+        // 1000 samples a second, so a 10 Hz vibrato repeats every 100 samples
+        let mut channel = stepping_channel(vec![0.0; 1000]);
+        channel.play_pitch(100.0);
+        channel.set_vibrato(1200.0, 10.0);
+        let base = channel.pitch;
+
+        let steps = oscillator_steps(&mut channel, 100);
+        let octaves: Vec<f32> = steps.iter().map(|step| (step / base).log2()).collect();
+        let highest = octaves.iter().copied().fold(f32::MIN, f32::max);
+        let lowest = octaves.iter().copied().fold(f32::MAX, f32::min);
+        let average = octaves.iter().sum::<f32>() / octaves.len() as f32;
+        assert!((highest - 1.0).abs() < 0.01, "{highest}");
+        assert!((lowest + 1.0).abs() < 0.01, "{lowest}");
+        assert!(average.abs() < 0.01, "{average}");
+        // It starts at the base pitch and rises first
+        assert!((steps[0] - base).abs() < 1e-6);
+        assert!(steps[10] > base);
+    }
+
+    #[test]
+    fn vibrato_of_zero_depth_holds_the_pitch() {
+        let mut channel = stepping_channel(vec![0.0; 1000]);
+        channel.play_pitch(100.0);
+        channel.set_vibrato(1200.0, 10.0);
+        channel.set_vibrato(0.0, 10.0);
+        let base = channel.pitch;
+        for step in oscillator_steps(&mut channel, 50) {
+            assert!((step - base).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn notes_are_equal_tempered_around_a4() {
+        assert_eq!(note_to_hz(0), 440.0);
+        assert_eq!(note_to_hz(12), 880.0);
+        assert_eq!(note_to_hz(-12), 220.0);
+        assert!((note_to_hz(3) - 523.2511).abs() < 1e-3);
+    }
+
+    #[test]
+    fn hz_to_note_inverts_note_to_hz_and_rounds() {
+        for note in -48..=48 {
+            assert_eq!(hz_to_note(note_to_hz(note)), note);
+        }
+        // A quarter tone sharp of A4 is still closest to A4
+        assert_eq!(hz_to_note(440.0 * 2f32.powf(0.4 / 12.0)), 0);
+        assert_eq!(hz_to_note(440.0 * 2f32.powf(0.6 / 12.0)), 1);
+    }
+
+    #[test]
+    fn note_names_count_from_a4_with_octaves_starting_at_c() {
+        assert_eq!(note("A4"), 0);
+        assert_eq!(note("C4"), -9);
+        assert_eq!(note("B3"), -10);
+        assert_eq!(note("C5"), 3);
+        assert_eq!(note("C#5"), 4);
+        assert_eq!(note("Db5"), 4);
+        assert_eq!(note("A##4"), 2);
+        assert_eq!(note("A-1"), -60);
+    }
+
+    #[test]
+    #[should_panic]
+    fn note_rejects_names_without_an_octave() {
+        note("C#");
+    }
+
+    #[test]
+    fn major_pentatonic_skips_the_fourth_and_seventh() {
+        let notes: Vec<i16> = (0..6)
+            .map(|degree| Scale::MAJOR_PENTATONIC.note(0, degree))
+            .collect();
+        assert_eq!(notes, [0, 2, 4, 7, 9, 12]);
+        // Nothing a semitone apart, so it never clashes
+        assert!(notes.windows(2).all(|pair| pair[1] - pair[0] >= 2));
+    }
+
+    #[test]
+    fn scale_degrees_wrap_into_other_octaves() {
+        assert_eq!(Scale::MAJOR.note(-9, 7), 3);
+        assert_eq!(Scale::MAJOR.note(-9, 9), 7);
+        assert_eq!(Scale::MAJOR.note(0, -1), -1);
+        assert_eq!(Scale::MINOR_PENTATONIC.note(0, -5), -12);
+        assert_eq!(Scale::MINOR_PENTATONIC.note(0, -4), -9);
+    }
+
+    #[test]
+    #[should_panic(expected = "rise within one octave")]
+    fn scales_have_to_rise() {
+        Scale::new(&[0, 4, 2]);
+    }
+
+    // Runs `sequencer` on `frames`, returning whether the channel was playing and the
+    // frequency it was set to after each
+    fn sequence(
+        sequencer: &mut Sequencer,
+        frames: impl IntoIterator<Item = u64>,
+    ) -> Vec<(u64, bool, f32)> {
+        let mut audio = OfflineAudio::new(1000);
+        let mut wrapper = audio.wrapper();
+        let channel = wrapper.add_synth_channel(vec![0.0; 4]);
+        assert_eq!(channel, AudioChannelId(0));
+        frames
+            .into_iter()
+            .map(|frame| {
+                sequencer.update(frame, &mut wrapper);
+                let channel = wrapper.get_channel(channel);
+                let hertz = (channel.pitch * channel.sample_rate).round();
+                (frame, channel.is_playing(), hertz)
+            })
+            .collect()
+    }
+
+    fn melody() -> Sequencer {
+        let channel = AudioChannelId(0);
+        Sequencer::new(vec![
+            (4, channel, SequencerEvent::Stop),
+            (0, channel, SequencerEvent::Pitch(100.0)),
+            (2, channel, SequencerEvent::Pitch(200.0)),
+        ])
+    }
+
+    #[test]
+    fn sequencer_fires_events_on_their_frames() {
+        let mut sequencer = melody();
+        let states = sequence(&mut sequencer, 10..16);
+        assert_eq!(
+            states,
+            [
+                (10, true, 100.0),
+                (11, true, 100.0),
+                (12, true, 200.0),
+                (13, true, 200.0),
+                (14, false, 0.0),
+                (15, false, 0.0),
+            ]
+        );
+        assert!(sequencer.is_finished());
+    }
+
+    #[test]
+    fn sequencer_catches_up_on_skipped_frames() {
+        let mut sequencer = melody();
+        let states = sequence(&mut sequencer, [0, 3]);
+        assert_eq!(states, [(0, true, 100.0), (3, true, 200.0)]);
+        assert!(!sequencer.is_finished());
+    }
+
+    #[test]
+    fn looping_sequencer_plays_again_each_loop() {
+        let mut sequencer = melody().looping(6);
+        let states = sequence(&mut sequencer, 0..13);
+        let playing: Vec<u64> = states
+            .iter()
+            .filter(|&&(_, is_playing, _)| is_playing)
+            .map(|&(frame, ..)| frame)
+            .collect();
+        assert_eq!(playing, [0, 1, 2, 3, 6, 7, 8, 9, 12]);
+        assert_eq!(states[7].2, 100.0);
+        assert!(!sequencer.is_finished());
+    }
+}
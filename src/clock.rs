@@ -0,0 +1,67 @@
+//! The source of time for the run loop, so tests can control time instead of waiting on it.
+
+#[cfg(test)]
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::{thread, time};
+
+pub(crate) trait Clock: Send + Sync {
+    /// Nanoseconds since the clock was created.
+    fn now_nanos(&self) -> u64;
+
+    /// Waits until `nanos` nanoseconds have passed.
+    fn sleep(&self, nanos: u64);
+}
+
+/// Follows the system's monotonic clock.
+pub(crate) struct RealClock {
+    start: time::Instant,
+}
+
+impl RealClock {
+    pub fn new() -> Self {
+        Self {
+            start: time::Instant::now(),
+        }
+    }
+}
+
+impl Clock for RealClock {
+    fn now_nanos(&self) -> u64 {
+        self.start.elapsed().as_nanos() as u64
+    }
+
+    fn sleep(&self, nanos: u64) {
+        thread::sleep(time::Duration::from_nanos(nanos));
+    }
+}
+
+/// Only moves when told to. Sleeping advances it instantly, so the run loop goes as fast as it
+/// can while seeing time pass exactly as it would in real time.
+#[cfg(test)]
+pub(crate) struct ManualClock {
+    nanos: AtomicU64,
+}
+
+#[cfg(test)]
+impl ManualClock {
+    pub fn new() -> Self {
+        Self {
+            nanos: AtomicU64::new(0),
+        }
+    }
+
+    pub fn advance(&self, nanos: u64) {
+        self.nanos.fetch_add(nanos, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+impl Clock for ManualClock {
+    fn now_nanos(&self) -> u64 {
+        self.nanos.load(Ordering::Relaxed)
+    }
+
+    fn sleep(&self, nanos: u64) {
+        self.advance(nanos);
+    }
+}
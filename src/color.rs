@@ -0,0 +1,347 @@
+//! Color conversions, palette animation and dithering.
+//!
+//! Colors are `[r, g, b]` like everywhere else. Hues are in degrees, from 0 to 360, and
+//! saturation and value from 0 to 1.
+
+/// Converts `rgb` to `(hue, saturation, value)`.
+pub fn rgb_to_hsv(rgb: [u8; 3]) -> (f32, f32, f32) {
+    let [r, g, b] = rgb.map(|channel| channel as f32 / 255.0);
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let delta = max - min;
+
+    let hue = if delta == 0.0 {
+        0.0
+    } else if max == r {
+        60.0 * ((g - b) / delta).rem_euclid(6.0)
+    } else if max == g {
+        60.0 * ((b - r) / delta + 2.0)
+    } else {
+        60.0 * ((r - g) / delta + 4.0)
+    };
+    let saturation = if max == 0.0 { 0.0 } else { delta / max };
+    (hue, saturation, max)
+}
+
+/// Converts `(hue, saturation, value)` to a color. The hue wraps around, so any angle works.
+pub fn hsv_to_rgb(hue: f32, saturation: f32, value: f32) -> [u8; 3] {
+    let hue = hue.rem_euclid(360.0);
+    let chroma = value * saturation;
+    let x = chroma * (1.0 - ((hue / 60.0) % 2.0 - 1.0).abs());
+    let (r, g, b) = match (hue / 60.0) as u32 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    let m = value - chroma;
+    [r, g, b].map(|channel| ((channel + m) * 255.0).round().clamp(0.0, 255.0) as u8)
+}
+
+/// Shifts the hue of `rgb` by `degrees`, keeping its saturation and brightness.
+pub fn rotate_hue(rgb: [u8; 3], degrees: f32) -> [u8; 3] {
+    let (hue, saturation, value) = rgb_to_hsv(rgb);
+    hsv_to_rgb(hue + degrees, saturation, value)
+}
+
+/// Moves every color in `palette` forward by `amount` places, wrapping around, so the color at
+/// index 0 ends up at index `amount`. Negative amounts move colors backwards.
+///
+/// Cycling a palette a step every few frames animates water, fire and the like for free when
+/// the game draws with palette indices.
+pub fn cycle_palette(palette: &mut [[u8; 3]], amount: isize) {
+    if palette.is_empty() {
+        return;
+    }
+    let shift = amount.rem_euclid(palette.len() as isize) as usize;
+    palette.rotate_right(shift);
+}
+
+/// Blends from `a` to `b` by `t`, which is clamped to `[0, 1]`. The blend is done in linear
+/// light rather than on the color values directly, so halfway between two colors looks as bright
+/// as it should instead of too dark.
+pub fn lerp_linear(a: [u8; 3], b: [u8; 3], t: f32) -> [u8; 3] {
+    let t = t.clamp(0.0, 1.0);
+    let mut rgb = [0; 3];
+    for ((channel, a), b) in rgb.iter_mut().zip(a).zip(b) {
+        let (a, b) = (srgb_to_linear(a), srgb_to_linear(b));
+        *channel = linear_to_srgb(a + (b - a) * t);
+    }
+    rgb
+}
+
+/// How [`dither`] spreads out the difference between the original colors and the palette.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DitherMode {
+    /// Every pixel becomes the closest palette color, so smooth gradients turn into bands.
+    #[default]
+    None,
+    /// Nudges each pixel by a 4x4 Bayer matrix before picking the closest color. Cheap, and the
+    /// same input always gives the same regular pattern, which also keeps animations from
+    /// shimmering.
+    Ordered,
+    /// Carries each pixel's error over to the pixels right and below it. Looks smoother than
+    /// [`DitherMode::Ordered`] on still images, but a small change anywhere can ripple through
+    /// the rest of the image.
+    FloydSteinberg,
+}
+
+const BAYER_4X4: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+/// Replaces every color in the `width` by `height` image in `pixels` with a color from `palette`,
+/// dithering as `mode` says so that gradients don't come out as flat bands. Does nothing if
+/// `palette` is empty.
+///
+/// To reduce colors to a number of shades per channel rather than a fixed palette, pass every
+/// combination of the shades.
+pub fn dither(
+    pixels: &mut [[u8; 3]],
+    width: u32,
+    height: u32,
+    palette: &[[u8; 3]],
+    mode: DitherMode,
+) {
+    if palette.is_empty() {
+        return;
+    }
+    let (width, height) = (width as usize, height as usize);
+    match mode {
+        DitherMode::None => {
+            for pixel in &mut pixels[..width * height] {
+                *pixel = closest_color(palette, pixel.map(|channel| channel as f32));
+            }
+        }
+        DitherMode::Ordered => {
+            // Roughly the distance between neighbouring palette colors if they were spread
+            // evenly through the color cube
+            let spread = 255.0 / (palette.len() as f32).cbrt();
+            for y in 0..height {
+                for x in 0..width {
+                    let threshold = (BAYER_4X4[y % 4][x % 4] as f32 + 0.5) / 16.0 - 0.5;
+                    let pixel = &mut pixels[y * width + x];
+                    *pixel = closest_color(
+                        palette,
+                        pixel.map(|channel| channel as f32 + threshold * spread),
+                    );
+                }
+            }
+        }
+        DitherMode::FloydSteinberg => {
+            let mut colors: Vec<[f32; 3]> = pixels[..width * height]
+                .iter()
+                .map(|pixel| pixel.map(|channel| channel as f32))
+                .collect();
+            for y in 0..height {
+                for x in 0..width {
+                    let index = y * width + x;
+                    let color = colors[index];
+                    let chosen = closest_color(palette, color);
+                    pixels[index] = chosen;
+
+                    let error: [f32; 3] = std::array::from_fn(|i| color[i] - chosen[i] as f32);
+                    let mut spread = |x: usize, y: usize, weight: f32| {
+                        if x < width && y < height {
+                            let color = &mut colors[y * width + x];
+                            for (channel, error) in color.iter_mut().zip(error) {
+                                *channel += error * weight;
+                            }
+                        }
+                    };
+                    spread(x + 1, y, 7.0 / 16.0);
+                    if x > 0 {
+                        spread(x - 1, y + 1, 3.0 / 16.0);
+                    }
+                    spread(x, y + 1, 5.0 / 16.0);
+                    spread(x + 1, y + 1, 1.0 / 16.0);
+                }
+            }
+        }
+    }
+}
+
+fn closest_color(palette: &[[u8; 3]], color: [f32; 3]) -> [u8; 3] {
+    let distance = |candidate: &[u8; 3]| -> f32 {
+        candidate
+            .iter()
+            .zip(color)
+            .map(|(&a, b)| (a as f32 - b).powi(2))
+            .sum()
+    };
+    *palette
+        .iter()
+        .min_by(|a, b| distance(a).total_cmp(&distance(b)))
+        .unwrap()
+}
+
+// The standard sRGB transfer function, from color values to light intensity from 0 to 1
+fn srgb_to_linear(channel: u8) -> f32 {
+    let channel = channel as f32 / 255.0;
+    if channel <= 0.04045 {
+        channel / 12.92
+    } else {
+        ((channel + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(linear: f32) -> u8 {
+    let channel = if linear <= 0.0031308 {
+        linear * 12.92
+    } else {
+        1.055 * linear.powf(1.0 / 2.4) - 0.055
+    };
+    (channel * 255.0).round().clamp(0.0, 255.0) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lerp_linear_hits_both_ends() {
+        let (a, b) = ([10, 200, 30], [250, 0, 128]);
+        assert_eq!(lerp_linear(a, b, 0.0), a);
+        assert_eq!(lerp_linear(a, b, 1.0), b);
+        assert_eq!(lerp_linear(a, b, -1.0), a);
+        assert_eq!(lerp_linear(a, b, 2.0), b);
+    }
+
+    #[test]
+    fn lerp_linear_halfway_is_half_the_light() {
+        // Half of white's light intensity is 188 in sRGB, not 128
+        assert_eq!(lerp_linear([0; 3], [255; 3], 0.5), [188; 3]);
+        assert_eq!(lerp_linear([255, 0, 0], [0, 0, 255], 0.5), [188, 0, 188]);
+    }
+
+    #[test]
+    fn srgb_conversion_round_trips_every_value() {
+        for value in 0..=255 {
+            assert_eq!(linear_to_srgb(srgb_to_linear(value)), value);
+        }
+    }
+
+    fn assert_close(a: [u8; 3], b: [u8; 3]) {
+        let close = a.iter().zip(b).all(|(&a, b)| a.abs_diff(b) <= 1);
+        assert!(close, "{a:?} != {b:?}");
+    }
+
+    #[test]
+    fn rotating_hue_a_full_turn_keeps_the_color() {
+        let colors = [
+            [255, 0, 0],
+            [12, 200, 90],
+            [200, 150, 100],
+            [30, 60, 90],
+            [0; 3],
+            [128; 3],
+            [255; 3],
+        ];
+        for color in colors {
+            assert_close(rotate_hue(color, 360.0), color);
+            assert_close(rotate_hue(color, -360.0), color);
+        }
+    }
+
+    #[test]
+    fn hue_rotation_wraps_around() {
+        assert_eq!(rotate_hue([255, 0, 0], 120.0), [0, 255, 0]);
+        assert_eq!(rotate_hue([255, 0, 0], -120.0), [0, 0, 255]);
+        assert_eq!(rotate_hue([255, 0, 0], 480.0), [0, 255, 0]);
+        let color = [200, 150, 100];
+        assert_close(rotate_hue(color, -90.0), rotate_hue(color, 270.0));
+        assert_close(rotate_hue(color, 450.0), rotate_hue(color, 90.0));
+        assert_eq!(hsv_to_rgb(-120.0, 1.0, 1.0), hsv_to_rgb(240.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn palettes_cycle_both_ways_and_wrap() {
+        let (a, b, c, d) = ([1; 3], [2; 3], [3; 3], [4; 3]);
+        let mut palette = [a, b, c, d];
+        cycle_palette(&mut palette, 1);
+        assert_eq!(palette, [d, a, b, c]);
+        cycle_palette(&mut palette, -2);
+        assert_eq!(palette, [b, c, d, a]);
+        cycle_palette(&mut palette, 5);
+        assert_eq!(palette, [a, b, c, d]);
+        cycle_palette(&mut palette, -7);
+        assert_eq!(palette, [d, a, b, c]);
+
+        let mut empty: [[u8; 3]; 0] = [];
+        cycle_palette(&mut empty, 3);
+        cycle_palette(&mut empty, -3);
+    }
+
+    const BLACK_AND_WHITE: [[u8; 3]; 2] = [[0; 3], [255; 3]];
+
+    fn flat_gray(width: u32, height: u32) -> Vec<[u8; 3]> {
+        vec![[128; 3]; (width * height) as usize]
+    }
+
+    fn white_count(pixels: &[[u8; 3]]) -> usize {
+        pixels.iter().filter(|&&pixel| pixel == [255; 3]).count()
+    }
+
+    #[test]
+    fn ordered_dithering_is_deterministic_and_tiles() {
+        let mut first = flat_gray(8, 8);
+        dither(&mut first, 8, 8, &BLACK_AND_WHITE, DitherMode::Ordered);
+        let mut second = flat_gray(8, 8);
+        dither(&mut second, 8, 8, &BLACK_AND_WHITE, DitherMode::Ordered);
+        assert_eq!(first, second);
+
+        // A flat color gives the same pattern in every 4x4 tile
+        for y in 0..8 {
+            for x in 0..8 {
+                assert_eq!(first[y * 8 + x], first[y % 4 * 8 + x % 4]);
+            }
+        }
+        // Mid gray comes out as half black and half white
+        assert_eq!(white_count(&first), 32);
+    }
+
+    #[test]
+    fn undithered_colors_band() {
+        let mut pixels = flat_gray(4, 4);
+        dither(&mut pixels, 4, 4, &BLACK_AND_WHITE, DitherMode::None);
+        assert!(pixels.iter().all(|&pixel| pixel == [255; 3]));
+    }
+
+    #[test]
+    fn floyd_steinberg_keeps_the_average_brightness() {
+        let mut pixels = flat_gray(16, 16);
+        dither(
+            &mut pixels,
+            16,
+            16,
+            &BLACK_AND_WHITE,
+            DitherMode::FloydSteinberg,
+        );
+        let whites = white_count(&pixels);
+        assert!((120..=136).contains(&whites), "{whites}");
+    }
+
+    #[test]
+    fn dithering_only_uses_palette_colors() {
+        let palette = [[255, 0, 0], [0, 0, 255], [20, 20, 20]];
+        for mode in [
+            DitherMode::None,
+            DitherMode::Ordered,
+            DitherMode::FloydSteinberg,
+        ] {
+            let mut pixels: Vec<[u8; 3]> = (0..64).map(|i| [i * 4, 100, 255 - i * 4]).collect();
+            dither(&mut pixels, 8, 8, &palette, mode);
+            assert!(
+                pixels.iter().all(|pixel| palette.contains(pixel)),
+                "{mode:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn dithering_with_an_empty_palette_does_nothing() {
+        let mut pixels = flat_gray(2, 2);
+        dither(&mut pixels, 2, 2, &[], DitherMode::Ordered);
+        assert_eq!(pixels, flat_gray(2, 2));
+    }
+}
@@ -0,0 +1,653 @@
+use crate::raster::{blit_clipped, line_points};
+use crate::text::{self, Font};
+
+pub use crate::raster::{
+    blend_pixel, blend_pixel_premultiplied, blit, fill_rect, line, rgb_pixels, rgb_pixels_mut,
+    set_pixel, DepthBuffer, Rect, Sprite, SubImage,
+};
+
+/// Draws `sprite` rotated clockwise by `angle` radians around its center, which is placed at
+/// `(cx, cy)`, clipped to the buffer.
+///
+/// Each covered pixel of the buffer is mapped back into the sprite and takes the color of the
+/// nearest sprite pixel, so the result stays sharp and has no gaps. Sprite pixels equal to
+/// `transparent` are skipped.
+#[allow(clippy::too_many_arguments)]
+pub fn blit_rotated(
+    pixels: &mut [[u8; 3]],
+    width: u32,
+    height: u32,
+    sprite: &Sprite,
+    cx: i32,
+    cy: i32,
+    angle: f32,
+    transparent: Option<[u8; 3]>,
+) {
+    let clip = Rect::new(0, 0, width, height);
+    blit_rotated_clipped(pixels, width, clip, sprite, cx, cy, angle, transparent);
+}
+
+// `blit_rotated`, but only drawing inside `clip`, which has to be inside the buffer
+#[allow(clippy::too_many_arguments)]
+fn blit_rotated_clipped(
+    pixels: &mut [[u8; 3]],
+    width: u32,
+    clip: Rect,
+    sprite: &Sprite,
+    cx: i32,
+    cy: i32,
+    angle: f32,
+    transparent: Option<[u8; 3]>,
+) {
+    let (sin, cos) = angle.sin_cos();
+    let (half_width, half_height) = (sprite.width() as f32 * 0.5, sprite.height() as f32 * 0.5);
+
+    // The bounding box of the rotated sprite
+    let extent_x = (half_width * cos.abs() + half_height * sin.abs()).ceil() as i32;
+    let extent_y = (half_width * sin.abs() + half_height * cos.abs()).ceil() as i32;
+    let rect = Rect::new(
+        cx - extent_x,
+        cy - extent_y,
+        extent_x as u32 * 2,
+        extent_y as u32 * 2,
+    )
+    .intersection(clip);
+
+    for dest_y in rect.y..rect.bottom() {
+        let dest_row = (dest_y as u32 * width) as usize;
+        for dest_x in rect.x..rect.right() {
+            // Rotate the pixel's center back by `angle` to find where it is in the sprite
+            let (dx, dy) = (
+                dest_x as f32 + 0.5 - cx as f32,
+                dest_y as f32 + 0.5 - cy as f32,
+            );
+            let src_x = (dx * cos + dy * sin + half_width).floor();
+            let src_y = (dy * cos - dx * sin + half_height).floor();
+            if src_x < 0.0
+                || src_y < 0.0
+                || src_x >= sprite.width() as f32
+                || src_y >= sprite.height() as f32
+            {
+                continue;
+            }
+
+            let color = sprite.get_pixel(src_x as u32, src_y as u32);
+            if Some(color) != transparent {
+                pixels[dest_row + dest_x as usize] = color;
+            }
+        }
+    }
+}
+
+/// Draws `sprite` between where it was at the previous fixed simulation step, `previous`, and
+/// where it is at the latest one, `current`, by `alpha`, normally [`Context::render_alpha`].
+/// This keeps motion smooth when frames don't line up with
+/// [`EngineBuilder::fixed_update_hz`] steps, without changing how often the game is simulated.
+/// The game has to keep both positions, since the engine doesn't know where anything is.
+///
+/// The position is rounded to the nearest pixel and is the sprite's top-left corner, as in
+/// [`blit`].
+///
+/// [`Context::render_alpha`]: crate::Context::render_alpha
+/// [`EngineBuilder::fixed_update_hz`]: crate::EngineBuilder::fixed_update_hz
+#[allow(clippy::too_many_arguments)]
+pub fn blit_interpolated(
+    pixels: &mut [[u8; 3]],
+    width: u32,
+    height: u32,
+    sprite: &Sprite,
+    previous: (f32, f32),
+    current: (f32, f32),
+    alpha: f32,
+    transparent: Option<[u8; 3]>,
+) {
+    let (x, y) = interpolate_position(previous, current, alpha);
+    blit(pixels, width, height, sprite, x, y, transparent);
+}
+
+#[inline]
+fn interpolate_position(previous: (f32, f32), current: (f32, f32), alpha: f32) -> (i32, i32) {
+    (
+        crate::ease::lerp(previous.0, current.0, alpha).round() as i32,
+        crate::ease::lerp(previous.1, current.1, alpha).round() as i32,
+    )
+}
+
+/// Fills `rect` with a gradient from `top` on its first row to `bottom` on its last, clipped to
+/// the buffer. Colors are blended as in [`color::lerp_linear`](crate::color::lerp_linear).
+pub fn fill_vertical_gradient(
+    pixels: &mut [[u8; 3]],
+    width: u32,
+    height: u32,
+    rect: Rect,
+    top: [u8; 3],
+    bottom: [u8; 3],
+) {
+    let clip = Rect::new(0, 0, width, height);
+    fill_gradient_clipped(pixels, width, clip, rect, top, bottom, true);
+}
+
+/// Fills `rect` with a gradient from `left` on its first column to `right` on its last, clipped
+/// to the buffer. Colors are blended as in [`color::lerp_linear`](crate::color::lerp_linear).
+pub fn fill_horizontal_gradient(
+    pixels: &mut [[u8; 3]],
+    width: u32,
+    height: u32,
+    rect: Rect,
+    left: [u8; 3],
+    right: [u8; 3],
+) {
+    let clip = Rect::new(0, 0, width, height);
+    fill_gradient_clipped(pixels, width, clip, rect, left, right, false);
+}
+
+// Fills the part of `rect` inside `clip`, which has to be inside the buffer. The gradient runs
+// across all of `rect`, so clipping doesn't change its colors
+fn fill_gradient_clipped(
+    pixels: &mut [[u8; 3]],
+    width: u32,
+    clip: Rect,
+    rect: Rect,
+    from: [u8; 3],
+    to: [u8; 3],
+    vertical: bool,
+) {
+    let visible = rect.intersection(clip);
+    let (start, steps) = if vertical {
+        (rect.y, rect.height)
+    } else {
+        (rect.x, rect.width)
+    };
+    // The first and last rows or columns get the end colors exactly
+    let color_at = |position: i32| {
+        let t = (position - start) as f32 / steps.saturating_sub(1).max(1) as f32;
+        crate::color::lerp_linear(from, to, t)
+    };
+
+    if vertical {
+        for y in visible.y..visible.bottom() {
+            let row = (y as u32 * width) as usize;
+            pixels[row + visible.x as usize..row + visible.right() as usize].fill(color_at(y));
+        }
+    } else {
+        // Computed once per column rather than for every pixel
+        let colors: Vec<[u8; 3]> = (visible.x..visible.right()).map(color_at).collect();
+        for y in visible.y..visible.bottom() {
+            let row = (y as u32 * width) as usize;
+            pixels[row + visible.x as usize..row + visible.right() as usize]
+                .copy_from_slice(&colors);
+        }
+    }
+}
+
+/// A pixel buffer bundled with its dimensions, so drawing doesn't need them passed in every
+/// time. Created with [`Context::draw`](crate::Context::draw).
+///
+/// Drawing goes through a camera: everything is drawn relative to the camera position set with
+/// [`set_camera`](Self::set_camera), then moved by the screen shake from
+/// [`Context::add_screen_shake`](crate::Context::add_screen_shake). The raw buffer from
+/// [`pixels`](Self::pixels) and [`clear`](Self::clear) aren't affected by either.
+///
+/// Drawing can also be limited to a region with [`push_clip`](Self::push_clip), which keeps
+/// things like the contents of a UI panel inside the panel.
+pub struct Canvas<'a> {
+    pixels: &'a mut [[u8; 3]],
+    width: u32,
+    height: u32,
+
+    camera: (i32, i32),
+    shake_offset: (i32, i32),
+    // Buffer pixels per unit of the coordinates drawn with, from `EngineBuilder::logical_size`
+    scale: (f32, f32),
+    // In buffer coordinates, each one inside the one before it
+    clip_stack: Vec<Rect>,
+}
+
+impl<'a> Canvas<'a> {
+    pub fn new(pixels: &'a mut [[u8; 3]], width: u32, height: u32) -> Self {
+        assert!((width * height) as usize == pixels.len());
+        Self {
+            pixels,
+            width,
+            height,
+
+            camera: (0, 0),
+            shake_offset: (0, 0),
+            scale: (1.0, 1.0),
+            clip_stack: Vec::new(),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn with_shake_offset(mut self, x: i32, y: i32) -> Self {
+        self.shake_offset = (x, y);
+        self
+    }
+
+    // Maps a `width` by `height` space onto the canvas
+    #[inline]
+    pub(crate) fn with_logical_size(mut self, width: u32, height: u32) -> Self {
+        self.scale = (
+            self.width as f32 / width as f32,
+            self.height as f32 / height as f32,
+        );
+        self
+    }
+
+    /// Sets the position that's drawn at the top-left corner of the canvas, so that drawing at
+    /// `(x, y)` puts it at `(x - camera_x, y - camera_y)` on screen. Screen shake is added on
+    /// top of this, so the camera can follow the player while shaking.
+    #[inline]
+    pub fn set_camera(&mut self, camera_x: i32, camera_y: i32) {
+        self.camera = (camera_x, camera_y);
+    }
+    #[inline]
+    pub fn camera(&self) -> (i32, i32) {
+        self.camera
+    }
+
+    // Converts a position to buffer coordinates
+    #[inline]
+    fn to_screen(&self, x: i32, y: i32) -> (i32, i32) {
+        let scale = |position: i32, scale: f32| (position as f32 * scale).floor() as i32;
+        (
+            scale(x - self.camera.0, self.scale.0) + self.shake_offset.0,
+            scale(y - self.camera.1, self.scale.1) + self.shake_offset.1,
+        )
+    }
+    // Scales both corners so that rectangles next to each other stay next to each other
+    #[inline]
+    fn rect_to_screen(&self, rect: Rect) -> Rect {
+        let (x, y) = self.to_screen(rect.x, rect.y);
+        let (right, bottom) = self.to_screen(rect.right(), rect.bottom());
+        Rect::new(x, y, (right - x).max(0) as u32, (bottom - y).max(0) as u32)
+    }
+
+    /// Limits drawing to `rect` until the matching [`pop_clip`](Self::pop_clip). Clip regions
+    /// nest: while one is active, pushing another limits drawing to the area inside both.
+    ///
+    /// `rect` goes through the camera like everything else, at the time it's pushed.
+    pub fn push_clip(&mut self, rect: Rect) {
+        let clip = self.rect_to_screen(rect).intersection(self.clip());
+        self.clip_stack.push(clip);
+    }
+    /// Removes the clip region from the last [`push_clip`](Self::push_clip), going back to the
+    /// one before it. Does nothing if there isn't one.
+    pub fn pop_clip(&mut self) {
+        self.clip_stack.pop();
+    }
+    /// The area that can currently be drawn to, in buffer coordinates. The whole canvas if no
+    /// clip region is pushed.
+    #[inline]
+    pub fn clip(&self) -> Rect {
+        self.clip_stack
+            .last()
+            .copied()
+            .unwrap_or(Rect::new(0, 0, self.width, self.height))
+    }
+
+    #[inline]
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+    #[inline]
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+    #[inline]
+    pub fn pixels(&mut self) -> &mut [[u8; 3]] {
+        self.pixels
+    }
+
+    #[inline]
+    pub fn clear(&mut self, color: [u8; 3]) {
+        self.pixels.fill(color);
+    }
+    #[inline]
+    pub fn set_pixel(&mut self, x: i32, y: i32, color: [u8; 3]) {
+        let (x, y) = self.to_screen(x, y);
+        if self.clip().contains(x, y) {
+            set_pixel(self.pixels, self.width, self.height, x, y, color);
+        }
+    }
+    #[inline]
+    pub fn fill_rect(&mut self, rect: Rect, color: [u8; 3]) {
+        let rect = self.rect_to_screen(rect).intersection(self.clip());
+        fill_rect(self.pixels, self.width, self.height, rect, color);
+    }
+    /// Fills `rect` with a gradient from `top` to `bottom`. See [`fill_vertical_gradient`].
+    #[inline]
+    pub fn fill_vertical_gradient(&mut self, rect: Rect, top: [u8; 3], bottom: [u8; 3]) {
+        let rect = self.rect_to_screen(rect);
+        fill_gradient_clipped(
+            self.pixels,
+            self.width,
+            self.clip(),
+            rect,
+            top,
+            bottom,
+            true,
+        );
+    }
+    /// Fills `rect` with a gradient from `left` to `right`. See [`fill_horizontal_gradient`].
+    #[inline]
+    pub fn fill_horizontal_gradient(&mut self, rect: Rect, left: [u8; 3], right: [u8; 3]) {
+        let rect = self.rect_to_screen(rect);
+        fill_gradient_clipped(
+            self.pixels,
+            self.width,
+            self.clip(),
+            rect,
+            left,
+            right,
+            false,
+        );
+    }
+    #[inline]
+    pub fn line(&mut self, x0: i32, y0: i32, x1: i32, y1: i32, color: [u8; 3]) {
+        let (x0, y0) = self.to_screen(x0, y0);
+        let (x1, y1) = self.to_screen(x1, y1);
+        let clip = self.clip();
+        line_points(x0, y0, x1, y1, |x, y| {
+            if clip.contains(x, y) {
+                self.pixels[(x as u32 + y as u32 * self.width) as usize] = color;
+            }
+        });
+    }
+    #[inline]
+    pub fn blit(&mut self, sprite: &Sprite, x: i32, y: i32, transparent: Option<[u8; 3]>) {
+        let (x, y) = self.to_screen(x, y);
+        blit_clipped(
+            self.pixels,
+            self.width,
+            self.clip(),
+            sprite,
+            x,
+            y,
+            transparent,
+        );
+    }
+    /// Draws `sprite` between `previous` and `current` by `alpha`. See [`blit_interpolated`].
+    #[inline]
+    pub fn blit_interpolated(
+        &mut self,
+        sprite: &Sprite,
+        previous: (f32, f32),
+        current: (f32, f32),
+        alpha: f32,
+        transparent: Option<[u8; 3]>,
+    ) {
+        let (x, y) = interpolate_position(previous, current, alpha);
+        self.blit(sprite, x, y, transparent);
+    }
+    /// Sets the pixel at `(x, y)` if `z` is nearer than what `depth` has there. See
+    /// [`DepthBuffer`].
+    #[inline]
+    pub fn set_pixel_z(&mut self, depth: &mut DepthBuffer, x: i32, y: i32, color: [u8; 3], z: f32) {
+        assert!(depth.width() == self.width && depth.height() == self.height);
+        let (x, y) = self.to_screen(x, y);
+        if self.clip().contains(x, y) {
+            depth.set_pixel(self.pixels, x, y, color, z);
+        }
+    }
+    /// Draws `sprite` at depth `z`, only over pixels where nothing nearer has been drawn. See
+    /// [`DepthBuffer`].
+    #[inline]
+    pub fn blit_z(
+        &mut self,
+        depth: &mut DepthBuffer,
+        sprite: &Sprite,
+        x: i32,
+        y: i32,
+        z: f32,
+        transparent: Option<[u8; 3]>,
+    ) {
+        assert!(depth.width() == self.width && depth.height() == self.height);
+        let (x, y) = self.to_screen(x, y);
+        depth.blit_clipped(self.pixels, self.clip(), sprite, x, y, z, transparent);
+    }
+    #[inline]
+    pub fn blit_rotated(
+        &mut self,
+        sprite: &Sprite,
+        cx: i32,
+        cy: i32,
+        angle: f32,
+        transparent: Option<[u8; 3]>,
+    ) {
+        let (cx, cy) = self.to_screen(cx, cy);
+        blit_rotated_clipped(
+            self.pixels,
+            self.width,
+            self.clip(),
+            sprite,
+            cx,
+            cy,
+            angle,
+            transparent,
+        );
+    }
+    #[inline]
+    pub fn text(&mut self, font: &Font, x: i32, y: i32, text: &str, color: [u8; 3]) {
+        let (x, y) = self.to_screen(x, y);
+        text::draw_text_clipped(
+            self.pixels,
+            self.width,
+            self.clip(),
+            font,
+            x,
+            y,
+            text,
+            color,
+        );
+    }
+    #[inline]
+    pub fn text_centered(&mut self, font: &Font, cx: i32, cy: i32, text: &str, color: [u8; 3]) {
+        let (cx, cy) = self.to_screen(cx, cy);
+        text::draw_text_centered_clipped(
+            self.pixels,
+            self.width,
+            self.clip(),
+            font,
+            cx,
+            cy,
+            text,
+            color,
+        );
+    }
+    /// Draws `text` over a copy of itself in `shadow_color`, moved by `shadow_offset`. An offset
+    /// of `(1, 1)` gives the usual one pixel drop shadow, which keeps text readable over busy
+    /// backgrounds.
+    #[allow(clippy::too_many_arguments)]
+    pub fn text_shadowed(
+        &mut self,
+        font: &Font,
+        x: i32,
+        y: i32,
+        text: &str,
+        color: [u8; 3],
+        shadow_color: [u8; 3],
+        shadow_offset: (i32, i32),
+    ) {
+        self.text(
+            font,
+            x + shadow_offset.0,
+            y + shadow_offset.1,
+            text,
+            shadow_color,
+        );
+        self.text(font, x, y, text, color);
+    }
+    /// Draws `text` with a one pixel outline in `outline_color` all the way around each glyph,
+    /// including the corners.
+    pub fn text_outlined(
+        &mut self,
+        font: &Font,
+        x: i32,
+        y: i32,
+        text: &str,
+        color: [u8; 3],
+        outline_color: [u8; 3],
+    ) {
+        for dy in -1..=1 {
+            for dx in -1..=1 {
+                if (dx, dy) != (0, 0) {
+                    self.text(font, x + dx, y + dy, text, outline_color);
+                }
+            }
+        }
+        self.text(font, x, y, text, color);
+    }
+
+    /// A view of `rect` within the canvas, limited to the current clip region. See
+    /// [`SubImage`].
+    #[inline]
+    pub fn sub_image(&mut self, rect: Rect) -> SubImage<'_> {
+        let rect = self.rect_to_screen(rect);
+        let clip = self.clip();
+        SubImage::new(self.pixels, self.width, rect).clipped_to(clip)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLACK: [u8; 3] = [0; 3];
+
+    // A 4x4 sprite where every pixel has a different color
+    fn numbered_sprite() -> Sprite {
+        Sprite::new(4, 4, (0..16).map(|i| [i as u8 + 1, 0, 0]).collect())
+    }
+
+    #[test]
+    fn blit_rotated_by_zero_copies_the_sprite() {
+        let sprite = numbered_sprite();
+        let mut pixels = vec![BLACK; 8 * 8];
+        blit_rotated(&mut pixels, 8, 8, &sprite, 4, 4, 0.0, None);
+        for y in 0..4 {
+            for x in 0..4 {
+                assert_eq!(
+                    pixels[(y + 2) * 8 + x + 2],
+                    sprite.get_pixel(x as u32, y as u32)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn blit_rotated_a_quarter_turn_transposes_and_mirrors() {
+        let sprite = numbered_sprite();
+        let mut pixels = vec![BLACK; 8 * 8];
+        blit_rotated(
+            &mut pixels,
+            8,
+            8,
+            &sprite,
+            4,
+            4,
+            std::f32::consts::FRAC_PI_2,
+            None,
+        );
+        // Turning clockwise is transposing, then mirroring left to right
+        for y in 0..4 {
+            for x in 0..4 {
+                assert_eq!(
+                    pixels[(y + 2) * 8 + x + 2],
+                    sprite.get_pixel(y as u32, 3 - x as u32),
+                    "at ({x}, {y})"
+                );
+            }
+        }
+        let drawn = pixels.iter().filter(|&&pixel| pixel != BLACK).count();
+        assert_eq!(drawn, 16);
+    }
+
+    #[test]
+    fn blit_rotated_skips_transparent_pixels() {
+        let sprite = Sprite::new(2, 1, vec![[9, 9, 9], [1, 1, 1]]);
+        let mut pixels = vec![BLACK; 4 * 4];
+        blit_rotated(&mut pixels, 4, 4, &sprite, 2, 2, 0.0, Some([9, 9, 9]));
+        assert_eq!(pixels[4 + 1], BLACK);
+        assert_eq!(pixels[4 + 2], [1, 1, 1]);
+    }
+
+    const WHITE: [u8; 3] = [255; 3];
+
+    // The bounds of every pixel that isn't black, as a rect
+    fn drawn_area(pixels: &[[u8; 3]], width: u32) -> Option<Rect> {
+        let drawn = (0..pixels.len() as i32).filter(|&i| pixels[i as usize] != BLACK);
+        let (xs, ys): (Vec<i32>, Vec<i32>) =
+            drawn.map(|i| (i % width as i32, i / width as i32)).unzip();
+        let (min_x, min_y) = (*xs.iter().min()?, *ys.iter().min()?);
+        let (max_x, max_y) = (*xs.iter().max()?, *ys.iter().max()?);
+        Some(Rect::new(
+            min_x,
+            min_y,
+            (max_x - min_x + 1) as u32,
+            (max_y - min_y + 1) as u32,
+        ))
+    }
+
+    #[test]
+    fn fill_rect_stays_inside_nested_clips() {
+        let mut pixels = vec![BLACK; 10 * 10];
+        let mut canvas = Canvas::new(&mut pixels, 10, 10);
+        canvas.push_clip(Rect::new(1, 1, 6, 6));
+        canvas.push_clip(Rect::new(4, 0, 6, 4));
+        canvas.fill_rect(Rect::new(0, 0, 10, 10), WHITE);
+        assert_eq!(drawn_area(&pixels, 10), Some(Rect::new(4, 1, 3, 3)));
+    }
+
+    #[test]
+    fn pop_clip_goes_back_to_the_outer_clip() {
+        let mut pixels = vec![BLACK; 10 * 10];
+        let mut canvas = Canvas::new(&mut pixels, 10, 10);
+        canvas.push_clip(Rect::new(2, 2, 4, 4));
+        canvas.push_clip(Rect::new(0, 0, 3, 3));
+        canvas.pop_clip();
+        assert_eq!(canvas.clip(), Rect::new(2, 2, 4, 4));
+        canvas.fill_rect(Rect::new(0, 0, 10, 10), WHITE);
+        assert_eq!(drawn_area(&pixels, 10), Some(Rect::new(2, 2, 4, 4)));
+    }
+
+    #[test]
+    fn clips_pushed_outside_the_canvas_draw_nothing() {
+        let mut pixels = vec![BLACK; 10 * 10];
+        let mut canvas = Canvas::new(&mut pixels, 10, 10);
+        canvas.push_clip(Rect::new(20, 20, 5, 5));
+        canvas.fill_rect(Rect::new(0, 0, 10, 10), WHITE);
+        canvas.set_pixel(5, 5, WHITE);
+        canvas.pop_clip();
+        canvas.pop_clip();
+        assert_eq!(drawn_area(&pixels, 10), None);
+    }
+
+    #[test]
+    fn clips_are_moved_by_the_camera_when_pushed() {
+        let mut pixels = vec![BLACK; 10 * 10];
+        let mut canvas = Canvas::new(&mut pixels, 10, 10);
+        canvas.set_camera(2, 1);
+        canvas.push_clip(Rect::new(3, 3, 2, 2));
+        canvas.set_camera(0, 0);
+        canvas.fill_rect(Rect::new(0, 0, 10, 10), WHITE);
+        assert_eq!(drawn_area(&pixels, 10), Some(Rect::new(1, 2, 2, 2)));
+    }
+
+    #[test]
+    fn vertical_gradient_goes_from_top_to_bottom_through_the_linear_average() {
+        let mut pixels = vec![BLACK; 2 * 3];
+        fill_vertical_gradient(&mut pixels, 2, 3, Rect::new(0, 0, 2, 3), BLACK, WHITE);
+        let middle = crate::color::lerp_linear(BLACK, WHITE, 0.5);
+        assert_eq!(pixels, [BLACK, BLACK, middle, middle, WHITE, WHITE]);
+    }
+
+    #[test]
+    fn clipped_gradients_keep_the_colors_of_the_whole_rect() {
+        let mut whole = vec![BLACK; 5];
+        fill_horizontal_gradient(&mut whole, 5, 1, Rect::new(0, 0, 5, 1), BLACK, WHITE);
+        let mut clipped = vec![BLACK; 3];
+        fill_horizontal_gradient(&mut clipped, 3, 1, Rect::new(-2, 0, 5, 1), BLACK, WHITE);
+        assert_eq!(clipped, whole[2..]);
+    }
+}
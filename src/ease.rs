@@ -0,0 +1,170 @@
+//! Interpolation and easing functions for animation.
+//!
+//! Easing functions take a progress `t` from 0 to 1 and return an eased progress that's 0 at
+//! `t = 0` and 1 at `t = 1`, to be passed to [`lerp`].
+
+#[inline]
+pub fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+/// Eases in and out along a cubic curve. `t` is clamped to `[0, 1]`.
+#[inline]
+pub fn smoothstep(t: f32) -> f32 {
+    let t = t.clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+#[inline]
+pub fn ease_in_quad(t: f32) -> f32 {
+    t * t
+}
+#[inline]
+pub fn ease_out_quad(t: f32) -> f32 {
+    1.0 - (1.0 - t) * (1.0 - t)
+}
+#[inline]
+pub fn ease_in_out_quad(t: f32) -> f32 {
+    if t < 0.5 {
+        2.0 * t * t
+    } else {
+        1.0 - 2.0 * (1.0 - t) * (1.0 - t)
+    }
+}
+
+#[inline]
+pub fn ease_in_cubic(t: f32) -> f32 {
+    t * t * t
+}
+#[inline]
+pub fn ease_out_cubic(t: f32) -> f32 {
+    1.0 - (1.0 - t).powi(3)
+}
+#[inline]
+pub fn ease_in_out_cubic(t: f32) -> f32 {
+    if t < 0.5 {
+        4.0 * t * t * t
+    } else {
+        1.0 - 4.0 * (1.0 - t).powi(3)
+    }
+}
+
+/// Animates a value from `from` to `to` over `duration` seconds.
+#[derive(Debug, Clone, Copy)]
+pub struct Tween {
+    from: f32,
+    to: f32,
+    duration: f32,
+    elapsed: f32,
+    easing: fn(f32) -> f32,
+}
+
+impl Tween {
+    /// Creates a tween that moves at a constant speed.
+    pub fn new(from: f32, to: f32, duration: f32) -> Self {
+        Self {
+            from,
+            to,
+            duration,
+            elapsed: 0.0,
+            easing: |t| t,
+        }
+    }
+
+    /// Sets the easing function, such as [`ease_out_cubic`].
+    #[inline]
+    pub fn easing(mut self, easing: fn(f32) -> f32) -> Self {
+        self.easing = easing;
+        self
+    }
+
+    /// Advances the tween by `dt` seconds, stopping at the end.
+    pub fn update(&mut self, dt: f32) {
+        self.elapsed = (self.elapsed + dt).min(self.duration);
+    }
+
+    /// Progress from 0 to 1, before easing.
+    #[inline]
+    pub fn progress(&self) -> f32 {
+        if self.duration > 0.0 {
+            self.elapsed / self.duration
+        } else {
+            1.0
+        }
+    }
+
+    #[inline]
+    pub fn value(&self) -> f32 {
+        lerp(self.from, self.to, (self.easing)(self.progress()))
+    }
+
+    #[inline]
+    pub fn is_finished(&self) -> bool {
+        self.elapsed >= self.duration
+    }
+
+    /// Starts the tween over from the beginning.
+    pub fn restart(&mut self) {
+        self.elapsed = 0.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Easing = fn(f32) -> f32;
+
+    const EASINGS: [(&str, Easing); 7] = [
+        ("smoothstep", smoothstep),
+        ("ease_in_quad", ease_in_quad),
+        ("ease_out_quad", ease_out_quad),
+        ("ease_in_out_quad", ease_in_out_quad),
+        ("ease_in_cubic", ease_in_cubic),
+        ("ease_out_cubic", ease_out_cubic),
+        ("ease_in_out_cubic", ease_in_out_cubic),
+    ];
+
+    #[test]
+    fn easings_hit_their_endpoints() {
+        for (name, easing) in EASINGS {
+            assert_eq!(easing(0.0), 0.0, "{name}(0)");
+            assert_eq!(easing(1.0), 1.0, "{name}(1)");
+        }
+    }
+
+    #[test]
+    fn easings_stay_in_range_and_never_go_backwards() {
+        for (name, easing) in EASINGS {
+            let mut last = 0.0;
+            for i in 0..=1000 {
+                let t = i as f32 / 1000.0;
+                let eased = easing(t);
+                assert!((0.0..=1.0).contains(&eased), "{name}({t}) = {eased}");
+                assert!(eased >= last, "{name} decreased at {t}");
+                last = eased;
+            }
+        }
+    }
+
+    #[test]
+    fn smoothstep_clamps_progress() {
+        assert_eq!(smoothstep(-1.0), 0.0);
+        assert_eq!(smoothstep(2.0), 1.0);
+    }
+
+    #[test]
+    fn tween_stops_at_its_end_value() {
+        let mut tween = Tween::new(10.0, 20.0, 2.0).easing(ease_in_quad);
+        tween.update(1.0);
+        assert_eq!(tween.value(), 12.5);
+        assert!(!tween.is_finished());
+
+        tween.update(5.0);
+        assert_eq!(tween.value(), 20.0);
+        assert!(tween.is_finished());
+
+        tween.restart();
+        assert_eq!(tween.value(), 10.0);
+    }
+}
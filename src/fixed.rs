@@ -0,0 +1,316 @@
+use std::{
+    fmt::Display,
+    ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign},
+};
+
+const FRAC_BITS: u32 = 16;
+const ONE: i32 = 1 << FRAC_BITS;
+
+/// A signed Q16.16 fixed-point number.
+///
+/// Arithmetic on `Fixed` gives the same result on every machine, unlike floats, so it can be used
+/// for simulations that have to stay in sync across replays or networked players. Multiplication
+/// and division round to the nearest representable value, with ties rounded away from zero.
+///
+/// Values range from -32768 to just under 32768. Like the integer types, the operators panic on
+/// overflow in debug builds and wrap around in release builds; the `checked_` methods return
+/// `None` instead.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Clone, Copy, Default)]
+pub struct Fixed(i32);
+
+impl Fixed {
+    pub const ZERO: Self = Self(0);
+    pub const ONE: Self = Self(ONE);
+    pub const MIN: Self = Self(i32::MIN);
+    pub const MAX: Self = Self(i32::MAX);
+    /// The smallest positive value, 2^-16.
+    pub const EPSILON: Self = Self(1);
+
+    #[inline]
+    pub const fn from_bits(bits: i32) -> Self {
+        Self(bits)
+    }
+    #[inline]
+    pub const fn to_bits(self) -> i32 {
+        self.0
+    }
+
+    #[inline]
+    pub const fn from_int(x: i32) -> Self {
+        Self(x << FRAC_BITS)
+    }
+    /// Converts from `f32`, rounding to the nearest representable value.
+    #[inline]
+    pub fn from_f32(x: f32) -> Self {
+        Self((x * ONE as f32).round() as i32)
+    }
+    #[inline]
+    pub fn to_f32(self) -> f32 {
+        self.0 as f32 / ONE as f32
+    }
+
+    /// Rounds toward negative infinity, which is the pixel that contains this coordinate.
+    #[inline]
+    pub const fn floor(self) -> i32 {
+        self.0 >> FRAC_BITS
+    }
+    #[inline]
+    pub const fn ceil(self) -> i32 {
+        (self.0 + (ONE - 1)) >> FRAC_BITS
+    }
+    /// Rounds to the nearest integer, with ties rounded up.
+    #[inline]
+    pub const fn round(self) -> i32 {
+        (self.0 + ONE / 2) >> FRAC_BITS
+    }
+
+    #[inline]
+    pub const fn abs(self) -> Self {
+        Self(self.0.abs())
+    }
+
+    #[inline]
+    pub const fn checked_add(self, rhs: Self) -> Option<Self> {
+        match self.0.checked_add(rhs.0) {
+            Some(bits) => Some(Self(bits)),
+            None => None,
+        }
+    }
+    #[inline]
+    pub const fn checked_sub(self, rhs: Self) -> Option<Self> {
+        match self.0.checked_sub(rhs.0) {
+            Some(bits) => Some(Self(bits)),
+            None => None,
+        }
+    }
+    /// Multiplies, returning `None` if the result is out of range.
+    #[inline]
+    pub fn checked_mul(self, rhs: Self) -> Option<Self> {
+        i32::try_from(self.wide_mul(rhs)).ok().map(Self)
+    }
+    /// Divides, returning `None` if `rhs` is zero or the result is out of range.
+    #[inline]
+    pub fn checked_div(self, rhs: Self) -> Option<Self> {
+        if rhs.0 == 0 {
+            return None;
+        }
+        i32::try_from(self.wide_div(rhs)).ok().map(Self)
+    }
+
+    // The rounded product as raw bits, which may not fit in an i32
+    #[inline]
+    fn wide_mul(self, rhs: Self) -> i64 {
+        let product = self.0 as i64 * rhs.0 as i64;
+        let half = 1 << (FRAC_BITS - 1);
+        if product >= 0 {
+            (product + half) >> FRAC_BITS
+        } else {
+            -((-product + half) >> FRAC_BITS)
+        }
+    }
+
+    // The rounded quotient as raw bits, which may not fit in an i32
+    #[inline]
+    fn wide_div(self, rhs: Self) -> i64 {
+        let (numerator, denominator) = ((self.0 as i64) << FRAC_BITS, rhs.0 as i64);
+        if (numerator < 0) == (denominator < 0) {
+            (numerator + denominator / 2) / denominator
+        } else {
+            (numerator - denominator / 2) / denominator
+        }
+    }
+}
+
+impl From<i32> for Fixed {
+    #[inline]
+    fn from(x: i32) -> Self {
+        Self::from_int(x)
+    }
+}
+
+impl From<Fixed> for f32 {
+    #[inline]
+    fn from(x: Fixed) -> Self {
+        x.to_f32()
+    }
+}
+
+impl Display for Fixed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(&self.to_f32(), f)
+    }
+}
+
+impl Add for Fixed {
+    type Output = Self;
+    #[inline]
+    fn add(self, rhs: Self) -> Self {
+        Self(self.0 + rhs.0)
+    }
+}
+
+impl Sub for Fixed {
+    type Output = Self;
+    #[inline]
+    fn sub(self, rhs: Self) -> Self {
+        Self(self.0 - rhs.0)
+    }
+}
+
+impl Mul for Fixed {
+    type Output = Self;
+    #[inline]
+    fn mul(self, rhs: Self) -> Self {
+        let product = self.wide_mul(rhs);
+        debug_assert!(
+            i32::try_from(product).is_ok(),
+            "attempt to multiply with overflow"
+        );
+        Self(product as i32)
+    }
+}
+
+impl Div for Fixed {
+    type Output = Self;
+    /// # Panics
+    ///
+    /// Panics if `rhs` is zero.
+    #[inline]
+    fn div(self, rhs: Self) -> Self {
+        let quotient = self.wide_div(rhs);
+        debug_assert!(
+            i32::try_from(quotient).is_ok(),
+            "attempt to divide with overflow"
+        );
+        Self(quotient as i32)
+    }
+}
+
+impl Neg for Fixed {
+    type Output = Self;
+    #[inline]
+    fn neg(self) -> Self {
+        Self(-self.0)
+    }
+}
+
+impl AddAssign for Fixed {
+    #[inline]
+    fn add_assign(&mut self, rhs: Self) {
+        *self = *self + rhs;
+    }
+}
+
+impl SubAssign for Fixed {
+    #[inline]
+    fn sub_assign(&mut self, rhs: Self) {
+        *self = *self - rhs;
+    }
+}
+
+impl MulAssign for Fixed {
+    #[inline]
+    fn mul_assign(&mut self, rhs: Self) {
+        *self = *self * rhs;
+    }
+}
+
+impl DivAssign for Fixed {
+    #[inline]
+    fn div_assign(&mut self, rhs: Self) {
+        *self = *self / rhs;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Deterministic values spread over -range..range, so failures are reproducible
+    fn values(count: usize, range: f32) -> impl Iterator<Item = f32> {
+        let mut state = 0x2545_f491_u32;
+        (0..count).map(move |_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            (state as f32 / u32::MAX as f32 * 2.0 - 1.0) * range
+        })
+    }
+
+    // Within rounding of the fixed-point result plus the error of `f32` itself
+    fn assert_close(actual: Fixed, expected: f32) {
+        let tolerance = 1.0 / ONE as f32 + expected.abs() * 1e-6;
+        assert!(
+            (actual.to_f32() - expected).abs() <= tolerance,
+            "{actual} != {expected}"
+        );
+    }
+
+    #[test]
+    fn converts_to_and_from_f32() {
+        for x in values(1000, 30000.0) {
+            assert_close(Fixed::from_f32(x), x);
+        }
+        assert_eq!(Fixed::from_f32(1.5).to_bits(), ONE + ONE / 2);
+        assert_eq!(Fixed::from_int(-3).to_f32(), -3.0);
+    }
+
+    #[test]
+    fn arithmetic_matches_f32() {
+        let pairs = values(1000, 100.0).zip(values(1001, 100.0).skip(1));
+        for (a, b) in pairs {
+            let (fa, fb) = (Fixed::from_f32(a), Fixed::from_f32(b));
+            let (a, b) = (fa.to_f32(), fb.to_f32());
+            assert_close(fa + fb, a + b);
+            assert_close(fa - fb, a - b);
+            assert_close(fa * fb, a * b);
+            if b.abs() >= 0.01 {
+                assert_close(fa / fb, a / b);
+            }
+        }
+    }
+
+    #[test]
+    fn rounds_ties_away_from_zero() {
+        let half_epsilon = Fixed::from_bits(1 << (FRAC_BITS / 2 - 1));
+        let product = Fixed::from_bits(1 << (FRAC_BITS / 2));
+        // 2^-9 * 2^-8 is half of the smallest step
+        assert_eq!(half_epsilon * product, Fixed::EPSILON);
+        assert_eq!(-half_epsilon * product, -Fixed::EPSILON);
+        assert_eq!(Fixed::EPSILON / Fixed::from_int(2), Fixed::EPSILON);
+        assert_eq!(-Fixed::EPSILON / Fixed::from_int(2), -Fixed::EPSILON);
+    }
+
+    #[test]
+    fn rounds_to_integers() {
+        let x = Fixed::from_f32(-1.5);
+        assert_eq!((x.floor(), x.ceil(), x.round()), (-2, -1, -1));
+        let x = Fixed::from_f32(2.25);
+        assert_eq!((x.floor(), x.ceil(), x.round()), (2, 3, 2));
+    }
+
+    #[test]
+    fn checked_operations_catch_overflow() {
+        let big = Fixed::from_int(30000);
+        assert_eq!(big.checked_mul(Fixed::from_int(2)), None);
+        assert_eq!(big.checked_div(Fixed::from_f32(0.5)), None);
+        assert_eq!(big.checked_div(Fixed::ZERO), None);
+        assert_eq!(big.checked_add(big), None);
+        assert_eq!(Fixed::MIN.checked_sub(Fixed::ONE), None);
+        assert_eq!(
+            big.checked_mul(Fixed::from_f32(0.5)),
+            Some(Fixed::from_int(15000))
+        );
+        assert_eq!(
+            Fixed::from_int(3).checked_div(Fixed::from_int(2)),
+            Some(Fixed::from_f32(1.5))
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "overflow")]
+    #[cfg(debug_assertions)]
+    fn multiplying_out_of_range_panics_in_debug_builds() {
+        let _ = Fixed::from_int(30000) * Fixed::from_int(2);
+    }
+}
@@ -0,0 +1,67 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::mpsc,
+};
+
+use notify::{EventKind, RecursiveMode, Watcher};
+
+// How long a file has to go without changing before the callback hears about it. Editors often
+// save a file in several steps, and exporting a whole folder touches files in a burst
+const DEBOUNCE_NANOS: u64 = 100_000_000;
+
+// Watches a directory for `EngineBuilder::watch_assets`. Changes are collected on the watcher's
+// thread and handed to the callback on the main thread between frames
+pub(crate) struct AssetWatcher {
+    // Stops watching when dropped
+    _watcher: notify::RecommendedWatcher,
+    events: mpsc::Receiver<notify::Result<notify::Event>>,
+    // The time each changed file last changed, until it settles down
+    pending: HashMap<PathBuf, u64>,
+    callback: Box<dyn FnMut(&Path)>,
+}
+
+impl AssetWatcher {
+    pub(crate) fn new(path: &Path, callback: Box<dyn FnMut(&Path)>) -> notify::Result<Self> {
+        let (sender, events) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(move |event| {
+            // The receiver is only gone once the engine is, and then nobody cares
+            let _ = sender.send(event);
+        })?;
+        watcher.watch(path, RecursiveMode::Recursive)?;
+        Ok(Self {
+            _watcher: watcher,
+            events,
+            pending: HashMap::new(),
+            callback,
+        })
+    }
+
+    // Calls the callback for every file that changed but has been left alone for a while
+    pub(crate) fn poll(&mut self, now_nanos: u64) {
+        for event in self.events.try_iter() {
+            let event = match event {
+                Ok(event) => event,
+                // Missing a change during development isn't worth stopping the game over
+                Err(_) => continue,
+            };
+            if matches!(
+                event.kind,
+                EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)
+            ) {
+                for path in event.paths {
+                    self.pending.insert(path, now_nanos);
+                }
+            }
+        }
+
+        let callback = &mut self.callback;
+        self.pending.retain(|path, changed_nanos| {
+            if now_nanos.saturating_sub(*changed_nanos) < DEBOUNCE_NANOS {
+                return true;
+            }
+            callback(path);
+            false
+        });
+    }
+}
@@ -0,0 +1,254 @@
+#[cfg(feature = "png")]
+use std::{error::Error, fmt::Display};
+
+#[cfg(feature = "png")]
+use crate::draw::Sprite;
+
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Copy)]
+pub enum ScalingFilter {
+    /// Copies the closest source pixel, keeping hard pixel edges.
+    Nearest,
+    /// Blends the four closest source pixels.
+    Bilinear,
+}
+
+/// Resizes an RGB image (3 bytes per pixel, rows top to bottom) to `dst_w` by `dst_h` pixels.
+pub fn scale_image(
+    src: &[u8],
+    src_w: u32,
+    src_h: u32,
+    dst_w: u32,
+    dst_h: u32,
+    filter: ScalingFilter,
+) -> Vec<u8> {
+    assert!((src_w * src_h * 3) as usize == src.len());
+
+    let mut dst = Vec::with_capacity((dst_w * dst_h * 3) as usize);
+    if src.is_empty() {
+        dst.resize((dst_w * dst_h * 3) as usize, 0);
+        return dst;
+    }
+
+    let src_pixel = |x: u32, y: u32| {
+        let index = ((x + y * src_w) * 3) as usize;
+        &src[index..index + 3]
+    };
+
+    match filter {
+        ScalingFilter::Nearest => {
+            for y in 0..dst_h {
+                let src_y = (y as u64 * src_h as u64 / dst_h as u64) as u32;
+                for x in 0..dst_w {
+                    let src_x = (x as u64 * src_w as u64 / dst_w as u64) as u32;
+                    dst.extend_from_slice(src_pixel(src_x, src_y));
+                }
+            }
+        }
+        ScalingFilter::Bilinear => {
+            // Maps a destination pixel's center onto the source image, returning the two source
+            // pixels on either side and how far along it is between them
+            let sample_points = |dst_pos: u32, dst_size: u32, src_size: u32| {
+                let pos = (dst_pos as f32 + 0.5) * src_size as f32 / dst_size as f32 - 0.5;
+                let pos = pos.clamp(0.0, (src_size - 1) as f32);
+                let low = pos as u32;
+                (low, (low + 1).min(src_size - 1), pos - low as f32)
+            };
+
+            for y in 0..dst_h {
+                let (y0, y1, fy) = sample_points(y, dst_h, src_h);
+                for x in 0..dst_w {
+                    let (x0, x1, fx) = sample_points(x, dst_w, src_w);
+                    let (p00, p10, p01, p11) = (
+                        src_pixel(x0, y0),
+                        src_pixel(x1, y0),
+                        src_pixel(x0, y1),
+                        src_pixel(x1, y1),
+                    );
+                    for channel in 0..3 {
+                        let lerp = |a: u8, b: u8, t: f32| a as f32 + (b as f32 - a as f32) * t;
+                        let top = lerp(p00[channel], p10[channel], fx);
+                        let bottom = lerp(p01[channel], p11[channel], fx);
+                        dst.push((top + (bottom - top) * fy).round() as u8);
+                    }
+                }
+            }
+        }
+    }
+
+    dst
+}
+
+#[cfg(feature = "png")]
+#[derive(Debug)]
+pub enum ImageError {
+    Decode(png::DecodingError),
+}
+
+#[cfg(feature = "png")]
+impl Display for ImageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Decode(err) => write!(f, "failed to decode image: {err}"),
+        }
+    }
+}
+
+#[cfg(feature = "png")]
+impl Error for ImageError {}
+
+#[cfg(feature = "png")]
+impl From<png::DecodingError> for ImageError {
+    fn from(err: png::DecodingError) -> Self {
+        Self::Decode(err)
+    }
+}
+
+#[cfg(feature = "png")]
+impl Sprite {
+    /// Decodes a PNG image of any color type.
+    ///
+    /// Palette, grayscale and 16-bit images are converted to 8-bit RGB. Sprites don't store
+    /// transparency, so the alpha channel of RGBA and grayscale-with-alpha images is ignored:
+    /// fully transparent pixels come out as whatever color they have underneath, which is often
+    /// black. Fill transparent areas with a color key in the image and pass it to [`blit`] to
+    /// leave those pixels out instead.
+    ///
+    /// [`blit`]: crate::draw::blit
+    pub fn from_png(bytes: &[u8]) -> Result<Sprite, ImageError> {
+        let mut decoder = png::Decoder::new(bytes);
+        decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+        let mut reader = decoder.read_info()?;
+        let mut buf = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buf)?;
+        let data = &buf[..info.buffer_size()];
+
+        let pixels = match info.color_type {
+            png::ColorType::Rgb => data.chunks_exact(3).map(|x| [x[0], x[1], x[2]]).collect(),
+            png::ColorType::Rgba => data.chunks_exact(4).map(|x| [x[0], x[1], x[2]]).collect(),
+            png::ColorType::Grayscale => data.iter().map(|&x| [x; 3]).collect(),
+            png::ColorType::GrayscaleAlpha => data.chunks_exact(2).map(|x| [x[0]; 3]).collect(),
+            // `EXPAND` turns palette images into RGB or RGBA
+            png::ColorType::Indexed => unreachable!("indexed PNG wasn't expanded"),
+        };
+
+        Ok(Sprite::new(info.width, info.height, pixels))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 2x2 RGB: red and green on the top row, blue and white below
+    #[cfg(feature = "png")]
+    const RGB_2X2: &[u8] = &[
+        0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a, 0x00, 0x00, 0x00, 0x0d, 0x49, 0x48, 0x44,
+        0x52, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x02, 0x08, 0x02, 0x00, 0x00, 0x00, 0xfd,
+        0xd4, 0x9a, 0x73, 0x00, 0x00, 0x00, 0x12, 0x49, 0x44, 0x41, 0x54, 0x78, 0xda, 0x63, 0xf8,
+        0xcf, 0xc0, 0xc0, 0x00, 0xc2, 0x0c, 0xff, 0x81, 0x00, 0x00, 0x1f, 0xee, 0x05, 0xfb, 0xf1,
+        0xab, 0xba, 0x77, 0x00, 0x00, 0x00, 0x00, 0x49, 0x45, 0x4e, 0x44, 0xae, 0x42, 0x60, 0x82,
+    ];
+
+    // 2x1 RGBA: opaque red, then fully transparent green
+    #[cfg(feature = "png")]
+    const RGBA_2X1: &[u8] = &[
+        0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a, 0x00, 0x00, 0x00, 0x0d, 0x49, 0x48, 0x44,
+        0x52, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x01, 0x08, 0x06, 0x00, 0x00, 0x00, 0xf4,
+        0x22, 0x7f, 0x8a, 0x00, 0x00, 0x00, 0x0f, 0x49, 0x44, 0x41, 0x54, 0x78, 0xda, 0x63, 0xf8,
+        0xcf, 0xc0, 0xf0, 0x1f, 0x08, 0x19, 0x00, 0x0f, 0xf9, 0x02, 0xfe, 0x94, 0x3c, 0x3d, 0xb3,
+        0x00, 0x00, 0x00, 0x00, 0x49, 0x45, 0x4e, 0x44, 0xae, 0x42, 0x60, 0x82,
+    ];
+
+    // 2x1 with a two-color palette, using the second color first
+    #[cfg(feature = "png")]
+    const INDEXED_2X1: &[u8] = &[
+        0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a, 0x00, 0x00, 0x00, 0x0d, 0x49, 0x48, 0x44,
+        0x52, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x01, 0x08, 0x03, 0x00, 0x00, 0x00, 0xc3,
+        0xfc, 0x8f, 0xb8, 0x00, 0x00, 0x00, 0x06, 0x50, 0x4c, 0x54, 0x45, 0x0a, 0x14, 0x1e, 0x28,
+        0x32, 0x3c, 0xd5, 0x1b, 0xb4, 0xe9, 0x00, 0x00, 0x00, 0x0b, 0x49, 0x44, 0x41, 0x54, 0x78,
+        0xda, 0x63, 0x60, 0x64, 0x00, 0x00, 0x00, 0x05, 0x00, 0x02, 0x42, 0xc2, 0x44, 0x9f, 0x00,
+        0x00, 0x00, 0x00, 0x49, 0x45, 0x4e, 0x44, 0xae, 0x42, 0x60, 0x82,
+    ];
+
+    #[test]
+    #[cfg(feature = "png")]
+    fn decodes_an_rgb_png() {
+        let sprite = Sprite::from_png(RGB_2X2).unwrap();
+        assert_eq!((sprite.width(), sprite.height()), (2, 2));
+        assert_eq!(sprite.get_pixel(1, 0), [0, 255, 0]);
+        assert_eq!(
+            sprite.pixels(),
+            [[255, 0, 0], [0, 255, 0], [0, 0, 255], [255, 255, 255]]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "png")]
+    fn decodes_an_rgba_png_without_its_alpha() {
+        let sprite = Sprite::from_png(RGBA_2X1).unwrap();
+        assert_eq!((sprite.width(), sprite.height()), (2, 1));
+        assert_eq!(sprite.pixels(), [[255, 0, 0], [0, 255, 0]]);
+    }
+
+    #[test]
+    #[cfg(feature = "png")]
+    fn decodes_a_palette_png() {
+        let sprite = Sprite::from_png(INDEXED_2X1).unwrap();
+        assert_eq!(sprite.pixels(), [[40, 50, 60], [10, 20, 30]]);
+    }
+
+    #[test]
+    #[cfg(feature = "png")]
+    fn rejects_data_that_isnt_a_png() {
+        assert!(matches!(
+            Sprite::from_png(&RGB_2X2[..20]),
+            Err(ImageError::Decode(_))
+        ));
+        assert!(Sprite::from_png(b"not a png").is_err());
+    }
+
+    #[test]
+    fn nearest_doubles_each_pixel_into_a_block() {
+        #[rustfmt::skip]
+        let src = [
+            1, 1, 1, 2, 2, 2,
+            3, 3, 3, 4, 4, 4,
+        ];
+        let dst = scale_image(&src, 2, 2, 4, 4, ScalingFilter::Nearest);
+        let values: Vec<u8> = dst.chunks_exact(3).map(|pixel| pixel[0]).collect();
+        #[rustfmt::skip]
+        let expected = [
+            1, 1, 2, 2,
+            1, 1, 2, 2,
+            3, 3, 4, 4,
+            3, 3, 4, 4,
+        ];
+        assert_eq!(values, expected);
+        assert!(dst
+            .chunks_exact(3)
+            .all(|pixel| pixel[0] == pixel[1] && pixel[1] == pixel[2]));
+    }
+
+    #[test]
+    fn nearest_downscales_by_skipping_pixels() {
+        let src: Vec<u8> = (0..4).flat_map(|value| [value; 3]).collect();
+        let dst = scale_image(&src, 4, 1, 2, 1, ScalingFilter::Nearest);
+        assert_eq!(dst, [0, 0, 0, 2, 2, 2]);
+    }
+
+    #[test]
+    fn bilinear_blends_between_neighbours() {
+        let src = [0, 0, 0, 200, 200, 200];
+        let dst = scale_image(&src, 2, 1, 4, 1, ScalingFilter::Bilinear);
+        let values: Vec<u8> = dst.chunks_exact(3).map(|pixel| pixel[0]).collect();
+        // Pixel centers land at -0.25, 0.25, 0.75 and 1.25 source pixels, clamped to the edges
+        assert_eq!(values, [0, 50, 150, 200]);
+    }
+
+    #[test]
+    fn bilinear_keeps_a_flat_color() {
+        let src = [10, 20, 30].repeat(9);
+        let dst = scale_image(&src, 3, 3, 7, 5, ScalingFilter::Bilinear);
+        assert!(dst.chunks_exact(3).all(|pixel| pixel == [10, 20, 30]));
+    }
+}
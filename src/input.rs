@@ -0,0 +1,157 @@
+use std::{collections::HashMap, hash::Hash};
+
+use crate::{Context, Key, MouseButton, PhysicalKey};
+
+/// A single input from the player, as reported by [`Context::input_events`].
+#[derive(PartialEq, Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum InputEvent {
+    Key {
+        key: Key,
+        pressed: bool,
+        /// Whether this is the operating system's auto-repeat for a held key, rather than an
+        /// actual press.
+        repeat: bool,
+    },
+    MouseButton {
+        button: MouseButton,
+        pressed: bool,
+    },
+    /// Scrolling by `(x, y)` steps. Positive `y` scrolls up.
+    Scroll {
+        x: f32,
+        y: f32,
+    },
+    /// A character typed by the player, after the keyboard layout and modifiers are applied.
+    Char(char),
+}
+
+/// An input that can trigger an action in an [`InputMap`].
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Binding {
+    Key(Key),
+    PhysicalKey(PhysicalKey),
+    MouseButton(MouseButton),
+}
+
+impl Binding {
+    fn is_pressed(self, ctx: &Context) -> bool {
+        match self {
+            Self::Key(key) => ctx.is_key_pressed(key),
+            Self::PhysicalKey(key) => ctx.is_physical_key_pressed(key),
+            Self::MouseButton(button) => ctx.is_mouse_button_pressed(button),
+        }
+    }
+    fn is_just_pressed(self, ctx: &Context) -> bool {
+        match self {
+            Self::Key(key) => ctx.is_key_just_pressed(key),
+            Self::PhysicalKey(key) => ctx.is_physical_key_just_pressed(key),
+            Self::MouseButton(button) => ctx.is_mouse_button_just_pressed(button),
+        }
+    }
+    fn is_just_released(self, ctx: &Context) -> bool {
+        match self {
+            Self::Key(key) => ctx.is_key_just_released(key),
+            Self::PhysicalKey(key) => ctx.is_physical_key_just_released(key),
+            Self::MouseButton(button) => ctx.is_mouse_button_just_released(button),
+        }
+    }
+}
+
+impl From<Key> for Binding {
+    #[inline]
+    fn from(key: Key) -> Self {
+        Self::Key(key)
+    }
+}
+
+impl From<PhysicalKey> for Binding {
+    #[inline]
+    fn from(key: PhysicalKey) -> Self {
+        Self::PhysicalKey(key)
+    }
+}
+
+impl From<MouseButton> for Binding {
+    #[inline]
+    fn from(button: MouseButton) -> Self {
+        Self::MouseButton(button)
+    }
+}
+
+/// Maps game actions, usually a user-defined enum, to the keys and mouse buttons that trigger
+/// them, so controls can be rebound while the game is running.
+///
+/// An action can have any number of bindings and counts as pressed while any of them is held.
+pub struct InputMap<A> {
+    bindings: HashMap<A, Vec<Binding>>,
+}
+
+impl<A: Eq + Hash> InputMap<A> {
+    pub fn new() -> Self {
+        Self {
+            bindings: HashMap::new(),
+        }
+    }
+
+    /// Adds `binding` to the bindings of `action`.
+    pub fn bind(&mut self, action: A, binding: impl Into<Binding>) {
+        let binding = binding.into();
+        let bindings = self.bindings.entry(action).or_default();
+        if !bindings.contains(&binding) {
+            bindings.push(binding);
+        }
+    }
+
+    /// Replaces all of the bindings of `action` with `binding`.
+    pub fn rebind(&mut self, action: A, binding: impl Into<Binding>) {
+        self.bindings.insert(action, vec![binding.into()]);
+    }
+
+    pub fn unbind(&mut self, action: &A, binding: impl Into<Binding>) {
+        let binding = binding.into();
+        if let Some(bindings) = self.bindings.get_mut(action) {
+            bindings.retain(|&x| x != binding);
+        }
+    }
+
+    pub fn clear_bindings(&mut self, action: &A) {
+        self.bindings.remove(action);
+    }
+
+    pub fn bindings(&self, action: &A) -> &[Binding] {
+        self.bindings.get(action).map_or(&[], Vec::as_slice)
+    }
+
+    /// Whether any of the bindings of `action` is held.
+    pub fn is_pressed(&self, ctx: &Context, action: A) -> bool {
+        self.bindings(&action)
+            .iter()
+            .any(|binding| binding.is_pressed(ctx))
+    }
+
+    /// Whether `action` started being pressed this frame: one of its bindings was pressed and
+    /// none of the others were already held.
+    pub fn is_just_pressed(&self, ctx: &Context, action: A) -> bool {
+        let bindings = self.bindings(&action);
+        bindings.iter().any(|binding| binding.is_just_pressed(ctx))
+            && !bindings
+                .iter()
+                .any(|binding| binding.is_pressed(ctx) && !binding.is_just_pressed(ctx))
+    }
+
+    /// Whether `action` stopped being pressed this frame: one of its bindings was released and
+    /// none of the others are still held.
+    pub fn is_just_released(&self, ctx: &Context, action: A) -> bool {
+        let bindings = self.bindings(&action);
+        bindings.iter().any(|binding| binding.is_just_released(ctx))
+            && !bindings.iter().any(|binding| binding.is_pressed(ctx))
+    }
+}
+
+impl<A: Eq + Hash> Default for InputMap<A> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
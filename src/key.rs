@@ -1,4 +1,5 @@
-#[derive(PartialEq, Eq, Hash, Debug, Clone, Copy)]
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Key {
     Num0,
     Num1,
@@ -81,9 +82,23 @@ pub enum Key {
     F12,
 }
 
+/// A key identified by where it is on the keyboard rather than what it's labeled, so that
+/// controls stay under the same fingers on every keyboard layout.
+///
+/// The wrapped [`Key`] names the key at that position on a US QWERTY keyboard:
+/// `PhysicalKey(Key::W)` is the key above `S`, even on an AZERTY keyboard where it's labeled Z.
+/// Use [`Key`] instead for shortcuts that should follow the letter printed on the key, like Q to
+/// quit. Physical keys are detected from the keyboard's scancodes, and modifiers don't
+/// distinguish left and right.
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PhysicalKey(pub Key);
+
 impl Key {
+    /// The key that types `char` without shift held. Letters are accepted in either case.
     pub fn from_char(char: u8) -> Option<Self> {
-        let key = match char {
+        let key = match char.to_ascii_lowercase() {
+            b' ' => Self::Space,
             b'0' => Self::Num0,
             b'1' => Self::Num1,
             b'2' => Self::Num2,
@@ -134,8 +149,10 @@ impl Key {
         };
         Some(key)
     }
+    /// The character the key types without shift held, with letters in lowercase.
     pub fn to_char(self) -> Option<u8> {
         let char = match self {
+            Self::Space => b' ',
             Self::Num0 => b'0',
             Self::Num1 => b'1',
             Self::Num2 => b'2',
@@ -187,3 +204,37 @@ impl Key {
         Some(char)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn space_maps_both_ways() {
+        assert_eq!(Key::from_char(b' '), Some(Key::Space));
+        assert_eq!(Key::Space.to_char(), Some(b' '));
+    }
+
+    #[test]
+    fn letters_are_read_in_either_case_and_written_in_lowercase() {
+        assert_eq!(Key::from_char(b'Q'), Some(Key::Q));
+        assert_eq!(Key::from_char(b'q'), Some(Key::Q));
+        assert_eq!(Key::Q.to_char(), Some(b'q'));
+    }
+
+    #[test]
+    fn every_mapped_character_round_trips() {
+        for char in 0..=127u8 {
+            if let Some(key) = Key::from_char(char) {
+                assert_eq!(key.to_char(), Some(char.to_ascii_lowercase()), "{char}");
+            }
+        }
+    }
+
+    #[test]
+    fn unmapped_characters_have_no_key() {
+        assert_eq!(Key::from_char(b'\n'), None);
+        assert_eq!(Key::from_char(b'!'), None);
+        assert_eq!(Key::Shift.to_char(), None);
+    }
+}
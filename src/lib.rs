@@ -1,16 +1,37 @@
+extern crate alloc;
+
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     error::Error,
     fmt::{Debug, Display},
+    sync::{mpsc, Arc},
+    time::Duration,
 };
 
 pub mod audio;
-
+pub mod color;
+pub mod draw;
+pub mod ease;
+pub mod fixed;
+pub mod image;
+pub mod input;
+pub mod particles;
+pub mod raster;
+pub mod text;
+pub mod ui;
+
+mod clock;
+#[cfg(feature = "hot-reload")]
+mod hot_reload;
 mod platform;
-use audio::{ActiveAudio, AudioWrapper};
+use audio::{ActiveAudio, AudioDeviceId, AudioWrapper, SharedAudio};
 use platform::{Window, WindowTrait};
 
-use crate::platform::WindowClient;
+use crate::clock::{Clock, RealClock};
+use crate::draw::{Canvas, Rect};
+use crate::input::InputEvent;
+use crate::platform::{BoundingBox, FrameTiming, Layer, WindowClient, WindowCommand};
+use crate::raster::Sprite;
 
 pub struct Icon {
     width: u32,
@@ -33,14 +54,54 @@ pub struct EngineBuilder {
     width: u32,
     height: u32,
     fullscreen: bool,
+    borderless: bool,
 
     title: String,
 
     icon: Option<Icon>,
+
+    target_fps: TargetFps,
+
+    audio_device: Option<AudioDeviceId>,
+    default_channel_volume: f32,
+
+    key_repeat: bool,
+
+    confirm_exit: bool,
+    intercept_close_shortcut: bool,
+
+    position: Option<(i32, i32)>,
+
+    fixed_update_hz: Option<u32>,
+
+    debug_overlay: bool,
+
+    run_frames: Option<u64>,
+
+    key_timeout_frames: Option<u64>,
+
+    post_process_shader: Option<String>,
+    display_effect: DisplayEffect,
+    transparent_color_key: Option<[u8; 3]>,
+
+    logical_size: Option<(u32, u32)>,
+
+    layer_count: usize,
+
+    border_image: Option<Sprite>,
+
+    pixel_aspect_ratio: f32,
+
+    premultiplied_alpha: bool,
+
+    on_slow_frame: Option<Box<dyn FnMut(Duration)>>,
+
+    #[cfg(feature = "hot-reload")]
+    watched_assets: Option<(std::path::PathBuf, AssetCallback)>,
 }
 
 mod key;
-pub use key::Key;
+pub use key::{Key, PhysicalKey};
 
 impl EngineBuilder {
     pub fn with_dimensions(width: u32, height: u32) -> Self {
@@ -50,6 +111,9 @@ impl EngineBuilder {
             ..Default::default()
         }
     }
+    /// Starts a fullscreen builder with every other setting at its default. To switch a builder
+    /// that's already been configured to fullscreen, use
+    /// [`fullscreen_dimensions`](Self::fullscreen_dimensions).
     pub fn fullscreen(target_width: u32, target_height: u32) -> Self {
         Self {
             width: target_width,
@@ -59,6 +123,27 @@ impl EngineBuilder {
         }
     }
 
+    /// Makes the window fullscreen with the given target dimensions, keeping the rest of the
+    /// builder's settings.
+    #[inline]
+    pub fn fullscreen_dimensions(mut self, target_width: u32, target_height: u32) -> Self {
+        self.width = target_width;
+        self.height = target_height;
+        self.fullscreen = true;
+        self
+    }
+
+    /// Covers the whole monitor with a window without a border or title bar, at the monitor's
+    /// current resolution. Unlike exclusive fullscreen the display mode never changes, so
+    /// switching to other windows is instant. The game is scaled to fit the monitor as it would
+    /// be in a window, or fills it entirely if combined with
+    /// [`fullscreen_dimensions`](Self::fullscreen_dimensions).
+    #[inline]
+    pub fn borderless_fullscreen(mut self) -> Self {
+        self.borderless = true;
+        self
+    }
+
     #[inline]
     pub fn icon(mut self, icon: Icon) -> Self {
         self.icon = Some(icon);
@@ -78,7 +163,279 @@ impl EngineBuilder {
         self
     }
 
+    #[inline]
+    pub fn target_fps(mut self, target_fps: TargetFps) -> Self {
+        self.target_fps = target_fps;
+        self
+    }
+
+    /// Decouples the game simulation from the frame rate. Each frame,
+    /// [`Context::fixed_updates`] reports how many steps of `1 / hz` seconds have elapsed and
+    /// should be simulated, and [`Context::render_alpha`] how far the frame is between the last
+    /// two steps, for interpolating what gets drawn.
+    #[inline]
+    pub fn fixed_update_hz(mut self, hz: u32) -> Self {
+        self.fixed_update_hz = Some(hz);
+        self
+    }
+
+    /// Shows the FPS, frame count, resolution and mouse position in the top-left corner, drawn
+    /// over the game after each frame. Can also be toggled with
+    /// [`Context::toggle_debug_overlay`].
+    #[inline]
+    pub fn debug_overlay(mut self, debug_overlay: bool) -> Self {
+        self.debug_overlay = debug_overlay;
+        self
+    }
+
+    /// Exits after `frames` frames, as if the game had called [`Context::exit`]. Meant for
+    /// automated tests, which can then check the final frame with [`Engine::pixels`].
+    #[inline]
+    pub fn run_frames(mut self, frames: u64) -> Self {
+        self.run_frames = Some(frames);
+        self
+    }
+
+    /// Releases keys that have been held for `frames` frames without any further events from the
+    /// operating system while the window is unfocused. A safety net for keys that would
+    /// otherwise stay pressed forever because their release was never reported, such as when
+    /// focus changes mid-press. Off by default.
+    #[inline]
+    pub fn key_timeout_frames(mut self, frames: u64) -> Self {
+        self.key_timeout_frames = Some(frames);
+        self
+    }
+
+    /// Draws the game through a full-screen fragment shader, for effects like blur, bloom or CRT
+    /// scanlines. The game is first rendered, scaled and letterboxed, into an offscreen texture
+    /// the size of the window, which the shader then draws to the window.
+    ///
+    /// `source` is a complete GLSL 3.30 fragment shader. It gets the rendered game as
+    /// `uniform sampler2D tex`, the window size in pixels as `uniform vec2 resolution`, and the
+    /// texture coordinate as `in vec2 uv`, and writes `out vec4 frag_color`. Where the game is
+    /// within the window is given as `uniform vec4 game_rect`, holding the texture coordinates of
+    /// its left, bottom, right and top edges, and its size in game pixels as
+    /// `uniform vec2 game_size`.
+    ///
+    /// Overrides [`display_effect`](Self::display_effect).
+    #[inline]
+    pub fn post_process_shader(mut self, source: String) -> Self {
+        self.post_process_shader = Some(source);
+        self
+    }
+
+    /// Draws the game with one of the built-in retro effects. A shortcut for
+    /// [`post_process_shader`](Self::post_process_shader) with a ready-made shader.
+    #[inline]
+    pub fn display_effect(mut self, effect: DisplayEffect) -> Self {
+        self.display_effect = effect;
+        self
+    }
+
+    /// Makes the window see-through wherever the game draws `key_color`, and in the letterbox
+    /// bars, so the desktop shows through; for overlays and desktop pets. The pixel buffer has
+    /// no alpha channel, so the key color stands in for transparent pixels and can't be shown
+    /// itself.
+    ///
+    /// Transparent windows depend on the platform: they need a compositing window manager on
+    /// Linux and aren't supported everywhere, in which case the key color shows up as is. A
+    /// [`post_process_shader`](Self::post_process_shader) has to pass the alpha of `tex` through
+    /// for transparency to survive it.
+    #[inline]
+    pub fn transparent_background(mut self, key_color: [u8; 3]) -> Self {
+        self.transparent_color_key = Some(key_color);
+        self
+    }
+
+    /// Lets the game draw in a fixed `width` by `height` space, no matter how large the pixel
+    /// buffer really is. Useful with [`fullscreen`](Self::fullscreen), where the buffer's size
+    /// depends on the monitor. Canvases from [`Context::draw`] scale positions and rectangle
+    /// sizes from this space to the buffer; sprites and text keep their size in pixels, only
+    /// their position is scaled. [`Context::logical_mouse_pos`] gives the mouse in the same
+    /// space.
+    ///
+    /// This only changes where things are drawn. Everything else, like timing and
+    /// [`Context::current_frame`], works the same.
+    #[inline]
+    pub fn logical_size(mut self, width: u32, height: u32) -> Self {
+        self.logical_size = Some((width, height));
+        self
+    }
+
+    /// Draws the game as `count` layers composited on the GPU instead of one, so parallax
+    /// backgrounds or a UI can be drawn and scrolled separately without blending them on the
+    /// CPU. Layer 0 is the pixel buffer passed to the frame callback; the others are RGBA
+    /// buffers from [`Context::layer`], drawn over it in order, blended by their alpha and moved
+    /// by [`Context::set_layer_offset`]. Defaults to 1, just the frame callback's buffer.
+    ///
+    /// Every extra layer is uploaded to the GPU each frame, which costs 4 bytes per pixel
+    /// whether the layer changed or not, so keep the count to what the game needs.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `count` is 0.
+    #[inline]
+    pub fn layer_count(mut self, count: usize) -> Self {
+        assert!(count > 0, "there has to be at least one layer");
+        self.layer_count = count;
+        self
+    }
+
+    /// Fills the letterbox around the game with `image` instead of black, for a decorative bezel
+    /// like on an arcade cabinet. The image is stretched over the whole window, smoothly rather
+    /// than in blocky pixels, and the game is drawn over it, so only the part outside the game
+    /// shows. No border is drawn by default.
+    ///
+    /// The border goes through [`post_process_shader`](Self::post_process_shader) along with
+    /// the game, and like the game it's see-through wherever it has the key color from
+    /// [`transparent_background`](Self::transparent_background).
+    #[inline]
+    pub fn border_image(mut self, image: Sprite) -> Self {
+        self.border_image = Some(image);
+        self
+    }
+
+    /// Draws each game pixel `ratio` times as wide as it is tall, for games made for systems
+    /// with non-square pixels, like the 8:7 pixels of some old consoles. Defaults to 1, for
+    /// square pixels.
+    ///
+    /// The game's dimensions stay the same; it's only stretched when drawn, and mouse
+    /// positions are mapped back to game pixels accordingly.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `ratio` isn't a positive number.
+    #[inline]
+    pub fn pixel_aspect_ratio(mut self, ratio: f32) -> Self {
+        assert!(
+            ratio > 0.0 && ratio.is_finite(),
+            "pixel aspect ratio must be positive, got {ratio}"
+        );
+        self.pixel_aspect_ratio = ratio;
+        self
+    }
+
+    /// Treats the colors in [`Context::layer`] buffers as premultiplied by their alpha when
+    /// blending the layers, so a pixel's red, green and blue must be no more than its alpha.
+    /// Premultiplied layers avoid dark fringes where opaque pixels meet transparent ones;
+    /// [`draw::blend_pixel_premultiplied`] blends them on the CPU the same way. Defaults to
+    /// straight alpha.
+    #[inline]
+    pub fn premultiplied_alpha(mut self, premultiplied: bool) -> Self {
+        self.premultiplied_alpha = premultiplied;
+        self
+    }
+
+    /// Places the window's top-left corner at `(x, y)` in screen coordinates.
+    #[inline]
+    pub fn position(mut self, x: i32, y: i32) -> Self {
+        self.position = Some((x, y));
+        self
+    }
+
+    /// Reports the operating system's key auto-repeat through [`Context::is_key_repeated`].
+    /// Off by default, so held keys only count as one press.
+    #[inline]
+    pub fn key_repeat(mut self, key_repeat: bool) -> Self {
+        self.key_repeat = key_repeat;
+        self
+    }
+
+    /// Keeps the game running when the window is asked to close, such as by its close button,
+    /// until the game answers with [`Context::confirm_exit`] or [`Context::cancel_exit`]. This
+    /// leaves time to show an "are you sure?" dialog. See [`Context::is_close_requested`].
+    ///
+    /// Off by default, so closing the window exits unless the game calls
+    /// [`Context::prevent_exit`] in the same frame.
+    #[inline]
+    pub fn confirm_exit(mut self, confirm_exit: bool) -> Self {
+        self.confirm_exit = confirm_exit;
+        self
+    }
+
+    /// Treats Alt+F4 pressed in the game window as a close request, going through the same flow
+    /// as the close button; see [`Context::is_close_requested`]. Combined with
+    /// [`confirm_exit`](Self::confirm_exit), this lets a game refuse to close.
+    ///
+    /// Where the shortcut reaches the window depends on the platform. On Windows and most Linux
+    /// desktops the system turns Alt+F4 into a close request itself, which already goes through
+    /// the close flow, so this only matters where it's passed on as a key press instead, such as
+    /// Linux without a window manager binding. On macOS the shortcut is Cmd+Q, which the system
+    /// always handles as a close request.
+    #[inline]
+    pub fn intercept_close_shortcut(mut self, intercept_close_shortcut: bool) -> Self {
+        self.intercept_close_shortcut = intercept_close_shortcut;
+        self
+    }
+
+    /// Plays audio through the given device instead of the default one. See
+    /// [`audio::output_devices`].
+    #[inline]
+    pub fn audio_device(mut self, id: AudioDeviceId) -> Self {
+        self.audio_device = Some(id);
+        self
+    }
+
+    /// The volume new audio channels start at, before [`AudioChannel::set_channel_volume`].
+    /// Defaults to 0.05, which sounds quiet on its own but leaves room for around twenty
+    /// channels playing at full volume before the mix clips, since channels are simply added
+    /// together. Games that only play a few sounds at once can raise it.
+    ///
+    /// [`AudioChannel::set_channel_volume`]: audio::AudioChannel::set_channel_volume
+    #[inline]
+    pub fn default_channel_volume(mut self, volume: f32) -> Self {
+        self.default_channel_volume = volume;
+        self
+    }
+
+    /// Calls `callback` with how long the frame took whenever a frame takes more than one and a
+    /// half times as long as it should, to help track down hitches, for example by logging
+    /// them. Costs nothing if it isn't set.
+    ///
+    /// Like [`Context::over_budget`], only the frame callback is timed, including any drawing it
+    /// does onto the pixel buffer. Sending the frame to the GPU and presenting it happen afterwards
+    /// and aren't included, so GPU stalls don't show up here.
+    #[inline]
+    pub fn on_slow_frame(mut self, callback: impl FnMut(Duration) + 'static) -> Self {
+        self.on_slow_frame = Some(Box::new(callback));
+        self
+    }
+
+    /// Watches the directory at `path` and everything in it, and calls `callback` with the path
+    /// of each file that's created, changed or removed, so the game can reload sprites and sounds
+    /// without restarting. Calling this again replaces the previous directory and callback.
+    ///
+    /// The callback runs on the main thread between frames. A file only counts as changed once
+    /// it's been left alone for a tenth of a second, so an editor saving in several steps only
+    /// triggers the callback once.
+    ///
+    /// Only available with the `hot-reload` feature, which is meant for development.
+    ///
+    /// # Panics
+    ///
+    /// [`build`](Self::build) panics if `path` can't be watched, for example because it
+    /// doesn't exist.
+    #[cfg(feature = "hot-reload")]
+    #[inline]
+    pub fn watch_assets(
+        mut self,
+        path: impl Into<std::path::PathBuf>,
+        callback: impl FnMut(&std::path::Path) + 'static,
+    ) -> Self {
+        self.watched_assets = Some((path.into(), Box::new(callback)));
+        self
+    }
+
+    /// # Panics
+    ///
+    /// Panics if the width or height is 0, which happens if neither were set.
     pub fn build(self) -> Engine {
+        assert!(
+            self.width > 0 && self.height > 0,
+            "engine dimensions must be at least 1x1, got {}x{}; set them with `dimensions`",
+            self.width,
+            self.height
+        );
         Engine::new(self)
     }
 }
@@ -89,12 +446,124 @@ impl Default for EngineBuilder {
             width: 0,
             height: 0,
             fullscreen: false,
+            borderless: false,
             title: String::from("Game"),
             icon: None,
+            target_fps: TargetFps::Fixed(60),
+            audio_device: None,
+            default_channel_volume: audio::DEFAULT_CHANNEL_VOLUME,
+            key_repeat: false,
+            confirm_exit: false,
+            intercept_close_shortcut: false,
+            position: None,
+            fixed_update_hz: None,
+            debug_overlay: false,
+            run_frames: None,
+            key_timeout_frames: None,
+            post_process_shader: None,
+            display_effect: DisplayEffect::None,
+            transparent_color_key: None,
+            logical_size: None,
+            layer_count: 1,
+            border_image: None,
+            pixel_aspect_ratio: 1.0,
+            premultiplied_alpha: false,
+            on_slow_frame: None,
+            #[cfg(feature = "hot-reload")]
+            watched_assets: None,
         }
     }
 }
 
+// The frame callback, and whether it runs on the update thread, where it has to be `Send`
+enum FrameCallback<F, T> {
+    Inline(F),
+    Threaded(T),
+}
+
+// Stands in for the callback type `FrameCallback` doesn't use
+type InlineOnly = fn(&mut Context, AudioWrapper, &mut [[u8; 3]]);
+
+// Steps the engine in place of the window's own run loop, for `Engine::run_stepped`
+type DriveFn<'a> = Box<dyn FnOnce(&mut EngineStep) + 'a>;
+
+// Called with the changed path, for `EngineBuilder::watch_assets`
+#[cfg(feature = "hot-reload")]
+type AssetCallback = Box<dyn FnMut(&std::path::Path)>;
+
+// What `EngineStep` controls, implemented by the run loop
+trait StepDriver {
+    fn poll_and_render(&mut self) -> bool;
+    fn read_framebuffer(&mut self) -> Sprite;
+}
+
+/// Advances an engine started with [`Engine::run_stepped`].
+pub struct EngineStep<'a> {
+    driver: &'a mut dyn StepDriver,
+}
+
+impl<'a> EngineStep<'a> {
+    /// Handles pending input, runs the frames that are due and draws, without waiting for the
+    /// next frame. Returns false once the game has exited or the window was closed, after which
+    /// it does nothing.
+    pub fn poll_and_render(&mut self) -> bool {
+        self.driver.poll_and_render()
+    }
+
+    /// Reads back the window as the GPU drew it, at the window's resolution, so tests can check
+    /// the scaling and letterboxing and not just the game's pixels. The current frame is drawn
+    /// again to read it, so this doesn't run any frames.
+    pub fn read_framebuffer(&mut self) -> Sprite {
+        self.driver.read_framebuffer()
+    }
+}
+
+/// Which GPU and driver the window is drawn with, as reported by OpenGL, for including in bug
+/// reports. See [`Engine::renderer_info`].
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Default)]
+pub struct RendererInfo {
+    /// The company behind the driver, like `"NVIDIA Corporation"`.
+    pub vendor: String,
+    /// The GPU, or the software renderer standing in for one.
+    pub renderer: String,
+    /// The OpenGL version and driver version.
+    pub version: String,
+}
+
+/// How presenting frames is synchronized with the monitor's refresh. See
+/// [`Context::set_swap_interval`].
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Copy)]
+pub enum SwapInterval {
+    /// Presents frames immediately, which can tear.
+    Off,
+    /// Waits for the monitor's next refresh before presenting a frame. The default.
+    On,
+    /// Waits for the next refresh like `On`, but presents immediately if a frame is late,
+    /// trading a little tearing for less stutter. Falls back to `On` where the driver doesn't
+    /// support it.
+    Adaptive,
+}
+
+/// Built-in effects applied to the whole window. See [`EngineBuilder::display_effect`].
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Copy, Default)]
+pub enum DisplayEffect {
+    #[default]
+    None,
+    /// Darkens the lower half of every row of game pixels, like the gaps between an old
+    /// monitor's scanlines.
+    Scanlines,
+    /// Bulges the screen outwards like a curved CRT, with faint scanlines and darkened edges.
+    CrtCurve,
+}
+
+/// How many times per second the frame callback runs.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum TargetFps {
+    Fixed(u32),
+    /// The refresh rate of the primary monitor, or 60 if it can't be determined.
+    MatchMonitor,
+}
+
 #[derive(PartialEq, Eq, Hash, Debug, Clone, Copy)]
 enum PressedState {
     JustPressed,
@@ -102,25 +571,156 @@ enum PressedState {
     JustReleased,
 }
 
+// Releases keys that have been held without any events since `timeout_frames` frames ago
+fn release_stuck_keys(
+    key_states: &mut HashMap<Key, PressedState>,
+    key_event_frames: &HashMap<Key, u64>,
+    current_frame: u64,
+    timeout_frames: u64,
+) {
+    for (key, state) in key_states.iter_mut() {
+        let last_event_frame = key_event_frames.get(key).copied().unwrap_or(0);
+        if *state == PressedState::Pressed && current_frame - last_event_frame >= timeout_frames {
+            *state = PressedState::JustReleased;
+        }
+    }
+}
+
+// Adds `delta` to the partial scroll in `remainder` and takes out the whole ticks. Scrolling the
+// other way drops the partial tick so that reversing direction responds immediately
+fn accumulate_scroll_ticks(remainder: &mut f32, delta: f32) -> i32 {
+    if delta * *remainder < 0.0 {
+        *remainder = 0.0;
+    }
+    *remainder += delta;
+    let ticks = remainder.trunc();
+    *remainder -= ticks;
+    ticks as i32
+}
+
+// A random offset that decays over time, from `Context::add_screen_shake`
+#[derive(Debug, Clone, Copy, Default)]
+struct ScreenShake {
+    intensity: f32,
+    duration: f32,
+    remaining: f32,
+}
+
+impl ScreenShake {
+    fn add(&mut self, intensity: f32, duration: f32) {
+        // Restart the fade from the current amplitude so the shake doesn't jump
+        self.intensity = self.amplitude() + intensity;
+        self.duration = self.remaining.max(duration);
+        self.remaining = self.duration;
+    }
+
+    fn update(&mut self, dt: f32) {
+        self.remaining = (self.remaining - dt).max(0.0);
+    }
+
+    // Falls off quadratically, so the shake settles down gently instead of stopping abruptly
+    fn amplitude(&self) -> f32 {
+        if self.remaining <= 0.0 {
+            return 0.0;
+        }
+        let t = self.remaining / self.duration;
+        self.intensity * t * t
+    }
+
+    // The offset for `frame`, which is the same for every canvas drawn that frame
+    fn offset(&self, frame: u64) -> (i32, i32) {
+        let amplitude = self.amplitude();
+        if amplitude <= 0.0 {
+            return (0, 0);
+        }
+        let random = |seed: u32| {
+            let hash = audio::simple_hash(seed);
+            hash as f32 / u32::MAX as f32 * 2.0 - 1.0
+        };
+        let seed = (frame as u32).wrapping_mul(2);
+        (
+            (random(seed) * amplitude).round() as i32,
+            (random(seed + 1) * amplitude).round() as i32,
+        )
+    }
+}
+
 #[derive(PartialEq, Eq, Hash, Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum MouseButton {
     Left,
     Middle,
     Right,
 }
 
+/// The input held down during one frame, as captured by [`Context::input_snapshot`]. Can be
+/// serialized with the `serde` feature, for saving replays.
+#[derive(PartialEq, Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct InputSnapshot {
+    pub keys: HashSet<Key>,
+    pub mouse_buttons: HashSet<MouseButton>,
+    pub mouse_pos: (f32, f32),
+}
+
+/// A standard system cursor shape.
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Copy, Default)]
+pub enum CursorIcon {
+    #[default]
+    Default,
+    Hand,
+    IBeam,
+    Crosshair,
+    HorizontalResize,
+    VerticalResize,
+    /// The image set with [`Context::set_custom_cursor`], or the default cursor if there isn't
+    /// one.
+    Custom,
+}
+
 pub struct Engine {
     width: u32,
     height: u32,
 
     window_width: u32,
     window_height: u32,
+    window_position: Option<(i32, i32)>,
+    window_opacity: f32,
+
+    target_fps: u32,
+    monitor_refresh_rate: Option<u32>,
+
+    key_repeat: bool,
+
+    confirm_exit: bool,
+    intercept_close_shortcut: bool,
+
+    fixed_update_hz: Option<u32>,
+
+    debug_overlay: bool,
+
+    run_frames: Option<u64>,
+
+    key_timeout_frames: Option<u64>,
+
+    logical_size: Option<(u32, u32)>,
+
+    on_slow_frame: Option<Box<dyn FnMut(Duration)>>,
+
+    #[cfg(feature = "hot-reload")]
+    asset_watcher: Option<hot_reload::AssetWatcher>,
 
     window: Option<Window>,
 
     audio: Option<ActiveAudio>,
 
     pixels: Vec<u8>,
+    // Every layer above the first, which is `pixels`
+    layers: Vec<Layer>,
+
+    renderer_info: RendererInfo,
+
+    clock: Arc<dyn Clock>,
 }
 
 impl Engine {
@@ -131,11 +731,55 @@ impl Engine {
             title,
             icon,
             fullscreen,
-            ..
+            borderless,
+            target_fps,
+            audio_device,
+            default_channel_volume,
+            key_repeat,
+            confirm_exit,
+            intercept_close_shortcut,
+            position,
+            fixed_update_hz,
+            debug_overlay,
+            run_frames,
+            key_timeout_frames,
+            post_process_shader,
+            display_effect,
+            transparent_color_key,
+            logical_size,
+            layer_count,
+            border_image,
+            pixel_aspect_ratio,
+            premultiplied_alpha,
+            on_slow_frame,
+            #[cfg(feature = "hot-reload")]
+            watched_assets,
         } = builder;
+        let post_process_shader = post_process_shader
+            .or_else(|| platform::display_effect_shader(display_effect).map(String::from));
 
-        let window = Window::new(width, height, &title, icon, fullscreen);
+        let mut window = Window::new(
+            width,
+            height,
+            &title,
+            icon,
+            fullscreen,
+            borderless,
+            platform::DisplaySettings {
+                post_process_shader,
+                transparent_color_key,
+                border_image,
+                pixel_aspect_ratio,
+                premultiplied_alpha,
+            },
+        );
+        if let Some((x, y)) = position {
+            window.set_window_position(x, y);
+        }
         let window_size = window.window_dimensions();
+        let window_position = (!fullscreen && !borderless).then(|| window.window_position());
+        let monitor_refresh_rate = window.monitor_refresh_rate();
+        let renderer_info = window.renderer_info();
 
         Self {
             width,
@@ -143,53 +787,362 @@ impl Engine {
 
             window_width: window_size.0,
             window_height: window_size.1,
+            window_position,
+            window_opacity: 1.0,
+
+            target_fps: match target_fps {
+                TargetFps::Fixed(fps) => fps,
+                TargetFps::MatchMonitor => monitor_refresh_rate.unwrap_or(60),
+            },
+            monitor_refresh_rate,
+
+            key_repeat,
+
+            confirm_exit,
+            intercept_close_shortcut,
+
+            fixed_update_hz,
+
+            debug_overlay,
+
+            run_frames,
+
+            key_timeout_frames,
 
             window: Some(window),
 
-            audio: ActiveAudio::new().unwrap_or_else(|err| panic!("{err:?}")),
+            audio: ActiveAudio::new(audio_device.as_ref(), default_channel_volume)
+                .unwrap_or_else(|err| panic!("{err:?}")),
 
             pixels: Vec::new(),
+            layers: vec![Layer::default(); layer_count - 1],
+
+            renderer_info,
+
+            logical_size,
+
+            on_slow_frame,
+
+            #[cfg(feature = "hot-reload")]
+            asset_watcher: watched_assets.map(|(path, callback)| {
+                hot_reload::AssetWatcher::new(&path, callback).unwrap_or_else(|err| {
+                    panic!("failed to watch assets at {}: {err}", path.display())
+                })
+            }),
+
+            clock: Arc::new(RealClock::new()),
         }
     }
 
+    /// Runs the game until the window is closed, calling `handle_frame` once per frame.
     pub fn run<F>(&mut self, handle_frame: F)
     where
-        F: FnMut(&mut Context, AudioWrapper, &mut [[u8; 3]]) -> (),
+        F: FnMut(&mut Context, AudioWrapper, &mut [[u8; 3]]),
+    {
+        self.run_with_driver(FrameCallback::<F, InlineOnly>::Inline(handle_frame), None);
+    }
+
+    /// Like [`run`](Self::run), but calls `handle_frame` on a separate thread, so the game can
+    /// draw the next frame while the previous one is being presented. This helps games whose
+    /// frames take a long time to compute, at the cost of each frame showing up one frame later.
+    ///
+    /// Changes the game makes to the window, like [`Context::set_window_position`] or
+    /// [`Context::toggle_debug_overlay`], also take effect a frame later.
+    ///
+    /// `handle_frame` has to be [`Send`] so that it can be moved to the other thread.
+    pub fn run_threaded<F>(&mut self, handle_frame: F)
+    where
+        F: FnMut(&mut Context, AudioWrapper, &mut [[u8; 3]]) + Send,
+    {
+        self.run_with_driver(FrameCallback::<InlineOnly, F>::Threaded(handle_frame), None);
+    }
+
+    /// Like [`run`](Self::run), but instead of looping and sleeping between frames itself, the
+    /// engine hands an [`EngineStep`] to `drive`, which advances the engine by calling
+    /// [`EngineStep::poll_and_render`] whenever it wants to. This is for fitting the engine into
+    /// another event loop or a benchmark harness.
+    ///
+    /// Frames still run at the target frame rate: each step runs the frames that have come due
+    /// since the last one, possibly none, and draws. The engine shuts down once `drive` returns.
+    pub fn run_stepped<F, D>(&mut self, handle_frame: F, drive: D)
+    where
+        F: FnMut(&mut Context, AudioWrapper, &mut [[u8; 3]]),
+        D: FnOnce(&mut EngineStep),
+    {
+        self.run_with_driver(
+            FrameCallback::<F, InlineOnly>::Inline(handle_frame),
+            Some(Box::new(drive)),
+        );
+    }
+
+    fn run_with_driver<F, T>(
+        &mut self,
+        handle_frame: FrameCallback<F, T>,
+        drive: Option<DriveFn<'_>>,
+    ) where
+        F: FnMut(&mut Context, AudioWrapper, &mut [[u8; 3]]),
+        T: FnMut(&mut Context, AudioWrapper, &mut [[u8; 3]]) + Send,
     {
         let pixel_buf_size = (self.width * self.height) as usize * 3;
         self.pixels.resize(pixel_buf_size, 0);
+        let layer_count = self.layers.len();
+        resize_layers(&mut self.layers, layer_count, self.width, self.height);
+
+        // Everything the frame callback sees, owned so that it can be sent to the update thread
+        struct FrameInput {
+            time_nanos: u64,
+            width: u32,
+            height: u32,
+            window_size: (u32, u32),
+            bounding_box: BoundingBox,
+            current_frame: u64,
+            monitor_refresh_rate: Option<u32>,
+            fixed_updates: u32,
+            render_alpha: f32,
+            fps: f32,
+            target_fps: u32,
+            over_budget: bool,
+            logical_size: Option<(u32, u32)>,
+            is_focused: bool,
+
+            mouse_pos: (f32, f32),
+            mouse_delta: (f32, f32),
+            is_mouse_in_window: bool,
+            was_mouse_in_window: bool,
+            is_mouse_captured: bool,
+            scroll_delta: (f32, f32),
+            scroll_ticks: (i32, i32),
+
+            mouse_button_states: HashMap<MouseButton, PressedState>,
+            key_states: HashMap<Key, PressedState>,
+            physical_key_states: HashMap<Key, PressedState>,
+            repeated_keys: HashSet<Key>,
+            input_events: Vec<InputEvent>,
+
+            window_position: Option<(i32, i32)>,
+            window_opacity: f32,
+            debug_overlay: bool,
+            screen_shake: ScreenShake,
+
+            close_requested: bool,
+            will_exit: bool,
+        }
+
+        // What the frame callback changed. Settings are only reported if the game changed them,
+        // so that a frame computed on the update thread can't undo changes made since it started
+        struct FrameOutput {
+            window_position: Option<(i32, i32)>,
+            window_opacity: Option<f32>,
+            window_commands: Vec<WindowCommand>,
+            cursor_icon: CursorIcon,
+            debug_overlay: Option<bool>,
+            added_screen_shakes: Vec<(f32, f32)>,
+            injected_events: Vec<InputEvent>,
+
+            // Whether the game answered the close request
+            close_resolved: bool,
+            will_exit: bool,
+        }
+
+        impl FrameInput {
+            fn run<F>(
+                &self,
+                handle_frame: &mut F,
+                audio: Option<&SharedAudio>,
+                pixels: &mut [u8],
+                layers: &mut [Layer],
+            ) -> FrameOutput
+            where
+                F: FnMut(&mut Context, AudioWrapper, &mut [[u8; 3]]),
+            {
+                let mut window_commands = Vec::new();
+                let mut ctx = Context {
+                    width: self.width,
+                    height: self.height,
+                    window_size: self.window_size,
+                    bounding_box: self.bounding_box,
+                    current_frame: self.current_frame,
+                    monitor_refresh_rate: self.monitor_refresh_rate,
+                    fixed_updates: self.fixed_updates,
+                    render_alpha: self.render_alpha,
+                    fps: self.fps,
+                    target_fps: self.target_fps,
+                    over_budget: self.over_budget,
+                    logical_size: self.logical_size,
+                    is_focused: self.is_focused,
+
+                    mouse_pos: self.mouse_pos,
+                    mouse_delta: self.mouse_delta,
+                    is_mouse_in_window: self.is_mouse_in_window,
+                    was_mouse_in_window: self.was_mouse_in_window,
+                    is_mouse_captured: self.is_mouse_captured,
+                    scroll_delta: self.scroll_delta,
+                    scroll_ticks: self.scroll_ticks,
+
+                    mouse_button_states: &self.mouse_button_states,
+
+                    key_states: &self.key_states,
+                    physical_key_states: &self.physical_key_states,
+                    repeated_keys: &self.repeated_keys,
+                    input_events: &self.input_events,
+
+                    window_position: self.window_position,
+                    window_opacity: self.window_opacity,
+                    window_commands: &mut window_commands,
+                    cursor_icon: CursorIcon::Default,
+                    debug_overlay: self.debug_overlay,
+                    raw_frame: None,
+                    layers,
+                    screen_shake: self.screen_shake,
+                    added_screen_shakes: Vec::new(),
+                    injected_events: Vec::new(),
+
+                    close_requested: self.close_requested,
+                    will_exit: self.will_exit,
+                };
+                handle_frame(
+                    &mut ctx,
+                    AudioWrapper::new(audio, self.time_nanos),
+                    draw::rgb_pixels_mut(pixels),
+                );
+                if let Some(raw_frame) = &ctx.raw_frame {
+                    pixels.copy_from_slice(raw_frame);
+                }
+
+                FrameOutput {
+                    window_position: ctx
+                        .window_position
+                        .filter(|_| ctx.window_position != self.window_position),
+                    window_opacity: (ctx.window_opacity != self.window_opacity)
+                        .then_some(ctx.window_opacity),
+                    cursor_icon: ctx.cursor_icon,
+                    debug_overlay: (ctx.debug_overlay != self.debug_overlay)
+                        .then_some(ctx.debug_overlay),
+                    added_screen_shakes: ctx.added_screen_shakes,
+                    injected_events: ctx.injected_events,
+                    close_resolved: self.close_requested && !ctx.close_requested,
+                    will_exit: ctx.will_exit,
+                    window_commands,
+                }
+            }
+        }
+
+        struct UpdateThread {
+            jobs: mpsc::Sender<(FrameInput, Vec<u8>, Vec<Layer>)>,
+            results: mpsc::Receiver<(FrameOutput, Vec<u8>, Vec<Layer>)>,
+            // The buffers for the next job to draw into, or None while a job is in progress
+            back_buffer: Option<(Vec<u8>, Vec<Layer>)>,
+        }
+
+        impl UpdateThread {
+            // Swaps the frame the update thread has been working on into `pixels` and starts it
+            // on the next one. Returns None for the first frame, when there's nothing to show
+            // yet, or an error if the update thread has panicked.
+            fn exchange(
+                &mut self,
+                input: FrameInput,
+                pixels: &mut Vec<u8>,
+                layers: &mut Vec<Layer>,
+            ) -> Result<Option<FrameOutput>, mpsc::RecvError> {
+                let mut output = None;
+                let (mut back_buffer, mut back_layers) = match self.back_buffer.take() {
+                    Some(buffers) => buffers,
+                    None => {
+                        let (finished_output, mut finished_pixels, mut finished_layers) =
+                            self.results.recv()?;
+                        // The window may have been resized while the frame was being drawn
+                        finished_pixels.resize(pixels.len(), 0);
+                        resize_layers(
+                            &mut finished_layers,
+                            layers.len(),
+                            input.width,
+                            input.height,
+                        );
+                        output = Some(finished_output);
+                        (
+                            std::mem::replace(pixels, finished_pixels),
+                            std::mem::replace(layers, finished_layers),
+                        )
+                    }
+                };
+                back_buffer.resize(pixels.len(), 0);
+                resize_layers(&mut back_layers, layers.len(), input.width, input.height);
+                // Offsets carry over like they would with a single set of layers
+                for (back_layer, layer) in back_layers.iter_mut().zip(layers.iter()) {
+                    back_layer.offset = layer.offset;
+                }
+                // If the thread is gone, the next call's recv reports it
+                let _ = self.jobs.send((input, back_buffer, back_layers));
+                Ok(output)
+            }
+        }
+
+        enum FrameHandler<F> {
+            Inline(F),
+            Threaded(UpdateThread),
+        }
 
         struct WindowRunner<'a, F>
         where
-            F: FnMut(&mut Context, AudioWrapper, &mut [[u8; 3]]) -> (),
+            F: FnMut(&mut Context, AudioWrapper, &mut [[u8; 3]]),
         {
             current_frame: u64,
 
-            bounding_box: (f32, f32, f32, f32),
+            last_frame_time: Option<u64>,
+            fixed_update_accumulator: u64,
+
+            fps: f32,
+            fps_frame_count: u32,
+            fps_window_start: u64,
+            // Whether the last call to `frame` took longer than a frame
+            over_budget: bool,
+
+            debug_overlay_font: Option<text::Font>,
+
+            bounding_box: BoundingBox,
 
             engine: &'a mut Engine,
-            handle_frame: F,
+            frame_handler: FrameHandler<F>,
 
             is_focused: bool,
 
             mouse_pos: (f32, f32),
+            // Where the mouse was last frame, for the mouse delta
+            last_mouse_pos: (f32, f32),
             is_mouse_in_window: bool,
+            was_mouse_in_window: bool,
+            // Scrolling since the last frame, and the partial ticks carried over between frames
+            scroll_delta: (f32, f32),
+            scroll_remainder: (f32, f32),
 
             mouse_button_states: HashMap<MouseButton, PressedState>,
             key_states: HashMap<Key, PressedState>,
-
-            will_exit: bool,
+            physical_key_states: HashMap<Key, PressedState>,
+            repeated_keys: HashSet<Key>,
+            input_events: Vec<InputEvent>,
+            // The frame each key last had an event on, for `EngineBuilder::key_timeout_frames`
+            key_event_frames: HashMap<Key, u64>,
+            physical_key_event_frames: HashMap<Key, u64>,
+
+            window_commands: Vec<WindowCommand>,
+            cursor_icon: CursorIcon,
+            screen_shake: ScreenShake,
+
+            // The window was asked to close and the game hasn't answered yet
+            close_requested: bool,
         }
 
         impl<'a, F> WindowClient for WindowRunner<'a, F>
         where
-            F: FnMut(&mut Context, AudioWrapper, &mut [[u8; 3]]) -> (),
+            F: FnMut(&mut Context, AudioWrapper, &mut [[u8; 3]]),
         {
             fn handle_event(&mut self, event: platform::WindowEvent) {
                 let engine = &mut self.engine;
 
                 match event {
                     platform::WindowEvent::MouseButton { button, pressed } => {
+                        self.input_events
+                            .push(InputEvent::MouseButton { button, pressed });
                         self.mouse_button_states.insert(
                             button,
                             if pressed {
@@ -199,7 +1152,24 @@ impl Engine {
                             },
                         );
                     }
-                    platform::WindowEvent::Key { key, pressed } => {
+                    platform::WindowEvent::Key { key, repeat, .. } if repeat => {
+                        self.key_event_frames.insert(key, self.current_frame);
+                        if engine.key_repeat {
+                            self.repeated_keys.insert(key);
+                            self.input_events.push(InputEvent::Key {
+                                key,
+                                pressed: true,
+                                repeat,
+                            });
+                        }
+                    }
+                    platform::WindowEvent::Key { key, pressed, .. } => {
+                        self.input_events.push(InputEvent::Key {
+                            key,
+                            pressed,
+                            repeat: false,
+                        });
+                        self.key_event_frames.insert(key, self.current_frame);
                         self.key_states.insert(
                             key,
                             if pressed {
@@ -208,35 +1178,53 @@ impl Engine {
                                 PressedState::JustReleased
                             },
                         );
+                        if pressed
+                            && engine.intercept_close_shortcut
+                            && key == Key::F4
+                            && self
+                                .key_states
+                                .get(&Key::Alt)
+                                .is_some_and(|state| *state != PressedState::JustReleased)
+                        {
+                            self.close_requested = true;
+                        }
+                    }
+                    platform::WindowEvent::PhysicalKey { key, pressed } => {
+                        self.physical_key_event_frames
+                            .insert(key, self.current_frame);
+                        self.physical_key_states.insert(
+                            key,
+                            if pressed {
+                                PressedState::JustPressed
+                            } else {
+                                PressedState::JustReleased
+                            },
+                        );
                     }
                     platform::WindowEvent::MouseEnter { entered } => {
                         self.is_mouse_in_window = entered
                     }
                     platform::WindowEvent::MousePos { x, y } => {
-                        let bounding_box = self.bounding_box;
-                        let half_dimensions = (
-                            engine.window_width as f32 * 0.5,
-                            engine.window_height as f32 * 0.5,
-                        );
-                        let (bounding_box_min_corner, bounding_box_dimensions) = (
-                            (
-                                bounding_box.0 * half_dimensions.0 + half_dimensions.0,
-                                bounding_box.1 * half_dimensions.1 + half_dimensions.1,
-                            ),
-                            (
-                                (bounding_box.2 - bounding_box.0) * half_dimensions.0,
-                                (bounding_box.3 - bounding_box.1) * half_dimensions.1,
-                            ),
-                        );
-                        self.mouse_pos = (
-                            (x as f32 - bounding_box_min_corner.0) / bounding_box_dimensions.0
-                                * engine.width as f32,
-                            (y as f32 - bounding_box_min_corner.1) / bounding_box_dimensions.1
-                                * engine.height as f32,
+                        self.mouse_pos = self.bounding_box.window_to_game(
+                            x as f32,
+                            y as f32,
+                            (engine.window_width, engine.window_height),
+                            (engine.width, engine.height),
                         );
                     }
+                    platform::WindowEvent::Scroll { x, y } => {
+                        self.scroll_delta.0 += x;
+                        self.scroll_delta.1 += y;
+                        self.input_events.push(InputEvent::Scroll { x, y })
+                    }
+                    platform::WindowEvent::Char(c) => self.input_events.push(InputEvent::Char(c)),
                     platform::WindowEvent::FocusChanged { focused } => self.is_focused = focused,
-                    platform::WindowEvent::WindowClose => self.will_exit = true,
+                    platform::WindowEvent::WindowClose => self.close_requested = true,
+                    platform::WindowEvent::WindowMove { x, y } => {
+                        if engine.window_position.is_some() {
+                            engine.window_position = Some((x, y));
+                        }
+                    }
                     platform::WindowEvent::WindowResize {
                         width,
                         height,
@@ -252,38 +1240,208 @@ impl Engine {
 
                         let pixel_buf_size = (width * height) as usize * 3;
                         engine.pixels.resize(pixel_buf_size, 0);
+                        let layer_count = engine.layers.len();
+                        resize_layers(&mut engine.layers, layer_count, width, height);
                         self.bounding_box = new_bounding_box;
                     }
                 }
             }
 
-            fn frame(&mut self, rand_source: u64) -> bool {
+            fn frame(&mut self, time_nanos: u64) -> bool {
+                let frame_start = self.engine.clock.now_nanos();
                 let engine = &mut self.engine;
 
-                let mut ctx = Context {
+                #[cfg(feature = "hot-reload")]
+                if let Some(asset_watcher) = &mut engine.asset_watcher {
+                    asset_watcher.poll(frame_start);
+                }
+
+                let (fixed_updates, render_alpha) = match engine.fixed_update_hz {
+                    Some(hz) => {
+                        let step_nanos = 1_000_000_000 / hz as u64;
+                        // Cap the catch-up after a stall so the game doesn't spiral trying to
+                        // simulate all of it at once
+                        let elapsed = self
+                            .last_frame_time
+                            .map_or(0, |last| time_nanos.saturating_sub(last))
+                            .min(250_000_000);
+                        self.fixed_update_accumulator += elapsed;
+                        let steps = self.fixed_update_accumulator / step_nanos;
+                        self.fixed_update_accumulator -= steps * step_nanos;
+                        (
+                            steps as u32,
+                            self.fixed_update_accumulator as f32 / step_nanos as f32,
+                        )
+                    }
+                    None => (1, 1.0),
+                };
+                self.last_frame_time = Some(time_nanos);
+
+                self.fps_frame_count += 1;
+                let fps_window = time_nanos.saturating_sub(self.fps_window_start);
+                if fps_window >= 1_000_000_000 {
+                    self.fps = self.fps_frame_count as f32 * 1e9 / fps_window as f32;
+                    self.fps_frame_count = 0;
+                    self.fps_window_start = time_nanos;
+                }
+
+                if let Some(timeout_frames) = engine.key_timeout_frames.filter(|_| !self.is_focused)
+                {
+                    release_stuck_keys(
+                        &mut self.key_states,
+                        &self.key_event_frames,
+                        self.current_frame,
+                        timeout_frames,
+                    );
+                    release_stuck_keys(
+                        &mut self.physical_key_states,
+                        &self.physical_key_event_frames,
+                        self.current_frame,
+                        timeout_frames,
+                    );
+                }
+
+                // While a button is held the mouse is captured: it counts as being in the window
+                // even if the cursor is dragged out, until the button is released
+                let is_mouse_captured = !self.mouse_button_states.is_empty();
+                let is_mouse_in_window = self.is_mouse_in_window || is_mouse_captured;
+
+                // Where the mouse was before it entered the window has nothing to do with where
+                // it is now, so it doesn't count as movement
+                let mouse_delta = if is_mouse_in_window && self.was_mouse_in_window {
+                    (
+                        self.mouse_pos.0 - self.last_mouse_pos.0,
+                        self.mouse_pos.1 - self.last_mouse_pos.1,
+                    )
+                } else {
+                    (0.0, 0.0)
+                };
+
+                let scroll_delta = std::mem::take(&mut self.scroll_delta);
+                let scroll_ticks = (
+                    accumulate_scroll_ticks(&mut self.scroll_remainder.0, scroll_delta.0),
+                    accumulate_scroll_ticks(&mut self.scroll_remainder.1, scroll_delta.1),
+                );
+
+                let input = FrameInput {
+                    time_nanos,
                     width: engine.width,
                     height: engine.height,
+                    window_size: (engine.window_width, engine.window_height),
+                    bounding_box: self.bounding_box,
                     current_frame: self.current_frame,
+                    monitor_refresh_rate: engine.monitor_refresh_rate,
+                    fixed_updates,
+                    render_alpha,
+                    fps: self.fps,
+                    target_fps: engine.target_fps,
+                    over_budget: self.over_budget,
+                    logical_size: engine.logical_size,
+                    is_focused: self.is_focused,
 
                     mouse_pos: self.mouse_pos,
-                    is_mouse_in_window: self.is_mouse_in_window,
-
-                    mouse_button_states: &self.mouse_button_states,
+                    mouse_delta,
+                    is_mouse_in_window,
+                    was_mouse_in_window: self.was_mouse_in_window,
+                    is_mouse_captured,
+                    scroll_delta,
+                    scroll_ticks,
+
+                    mouse_button_states: self.mouse_button_states.clone(),
+                    key_states: self.key_states.clone(),
+                    physical_key_states: self.physical_key_states.clone(),
+                    repeated_keys: self.repeated_keys.clone(),
+                    input_events: std::mem::take(&mut self.input_events),
+
+                    window_position: engine.window_position,
+                    window_opacity: engine.window_opacity,
+                    debug_overlay: engine.debug_overlay,
+                    screen_shake: self.screen_shake,
+
+                    close_requested: self.close_requested,
+                    // Without `EngineBuilder::confirm_exit`, a close request exits unless the
+                    // game prevents it right away
+                    will_exit: self.close_requested && !engine.confirm_exit,
+                };
+                let output = match &mut self.frame_handler {
+                    FrameHandler::Inline(handle_frame) => {
+                        let audio = engine.audio.as_ref().map(ActiveAudio::shared);
+                        Some(input.run(
+                            handle_frame,
+                            audio.as_ref(),
+                            &mut engine.pixels,
+                            &mut engine.layers,
+                        ))
+                    }
+                    FrameHandler::Threaded(update_thread) => {
+                        match update_thread.exchange(input, &mut engine.pixels, &mut engine.layers)
+                        {
+                            Ok(output) => output,
+                            // Stop running so the panic is passed on when the thread is joined
+                            Err(_) => return false,
+                        }
+                    }
+                };
 
-                    key_states: &self.key_states,
+                let mut will_exit = false;
+                let mut injected_events = Vec::new();
+                if let Some(output) = output {
+                    will_exit = output.will_exit;
+                    injected_events = output.injected_events;
+                    if output.close_resolved {
+                        self.close_requested = false;
+                    }
+                    if let Some(window_position) = output.window_position {
+                        engine.window_position = Some(window_position);
+                    }
+                    if let Some(window_opacity) = output.window_opacity {
+                        engine.window_opacity = window_opacity;
+                    }
+                    if let Some(debug_overlay) = output.debug_overlay {
+                        engine.debug_overlay = debug_overlay;
+                    }
+                    for (intensity, duration) in output.added_screen_shakes {
+                        self.screen_shake.add(intensity, duration);
+                    }
+                    self.window_commands.extend(output.window_commands);
+                    if output.cursor_icon != self.cursor_icon {
+                        self.cursor_icon = output.cursor_icon;
+                        self.window_commands
+                            .push(WindowCommand::CursorIcon(output.cursor_icon));
+                    }
+                }
 
-                    will_exit: self.will_exit,
-                };
-                (self.handle_frame)(
-                    &mut ctx,
-                    AudioWrapper::new(engine.audio.as_mut(), rand_source),
-                    bytemuck::try_cast_slice_mut(engine.pixels.as_mut_slice()).unwrap(),
-                );
+                if engine.debug_overlay {
+                    let (mouse_x, mouse_y) = mouse_pixel(self.mouse_pos);
+                    let overlay_text = format!(
+                        "{:.0} FPS\nFRAME {}\n{}X{}\nMOUSE {},{}",
+                        self.fps, self.current_frame, engine.width, engine.height, mouse_x, mouse_y,
+                    );
+                    let font = self.debug_overlay_font.get_or_insert_with(Default::default);
+                    let (text_width, text_height) = text::measure_text(font, &overlay_text);
+                    let pixels = draw::rgb_pixels_mut(&mut engine.pixels);
+                    let (width, height) = (engine.width, engine.height);
+                    let background = draw::Rect::new(0, 0, text_width + 2, text_height + 2);
+                    draw::fill_rect(pixels, width, height, background, [0, 0, 0]);
+                    text::draw_text(
+                        pixels,
+                        width,
+                        height,
+                        font,
+                        1,
+                        1,
+                        &overlay_text,
+                        [255, 255, 0],
+                    );
+                }
 
                 self.current_frame += 1;
+                self.screen_shake.update(1.0 / engine.target_fps as f32);
 
-                let will_exit = !ctx.will_exit;
+                self.was_mouse_in_window = is_mouse_in_window;
+                self.last_mouse_pos = self.mouse_pos;
 
+                self.repeated_keys.clear();
                 self.key_states
                     .retain(|_, state| *state != PressedState::JustReleased);
                 for (_, state) in self.key_states.iter_mut() {
@@ -291,6 +1449,13 @@ impl Engine {
                         *state = PressedState::Pressed;
                     }
                 }
+                self.physical_key_states
+                    .retain(|_, state| *state != PressedState::JustReleased);
+                for (_, state) in self.physical_key_states.iter_mut() {
+                    if *state == PressedState::JustPressed {
+                        *state = PressedState::Pressed;
+                    }
+                }
                 self.mouse_button_states
                     .retain(|_, state| *state != PressedState::JustReleased);
                 for (_, state) in self.mouse_button_states.iter_mut() {
@@ -299,34 +1464,169 @@ impl Engine {
                     }
                 }
 
-                will_exit
+                let out_of_frames = engine
+                    .run_frames
+                    .is_some_and(|frames| self.current_frame >= frames);
+
+                let frame_nanos = engine.clock.now_nanos() - frame_start;
+                let target_frame_nanos = 1_000_000_000 / engine.target_fps as u64;
+                self.over_budget = frame_nanos > target_frame_nanos;
+                if let Some(on_slow_frame) = &mut engine.on_slow_frame {
+                    if frame_nanos > target_frame_nanos * 3 / 2 {
+                        on_slow_frame(Duration::from_nanos(frame_nanos));
+                    }
+                }
+
+                // Handled after this frame's input is cleared so they show up in the next one,
+                // before any real events that arrive in the meantime
+                for event in injected_events {
+                    self.handle_event(event.into());
+                }
+
+                !will_exit && !out_of_frames
+            }
+
+            fn take_commands(&mut self) -> Vec<WindowCommand> {
+                std::mem::take(&mut self.window_commands)
             }
 
             fn get_pixels(&self) -> &[u8] {
                 &self.engine.pixels
             }
 
-            fn get_bounding_box(&self) -> (f32, f32, f32, f32) {
-                self.bounding_box
+            fn get_layers(&self) -> &[Layer] {
+                &self.engine.layers
             }
         }
 
         let mut window = self.window.take().unwrap();
-        window.run(&mut WindowRunner {
-            bounding_box: window.current_bounding_box(),
-            current_frame: 0,
-            engine: self,
-            handle_frame,
+        let target_fps = self.target_fps;
+        let clock = self.clock.clone();
+        let audio = self.audio.as_ref().map(ActiveAudio::shared);
+        std::thread::scope(|scope| {
+            let frame_handler = match handle_frame {
+                FrameCallback::Threaded(handle_frame) => {
+                    let (job_sender, jobs) = mpsc::channel::<(FrameInput, Vec<u8>, Vec<Layer>)>();
+                    let (result_sender, results) = mpsc::channel();
+                    scope.spawn(move || {
+                        let mut handle_frame = handle_frame;
+                        // Ends once the runner, and with it the job sender, is dropped
+                        for (input, mut pixels, mut layers) in jobs {
+                            let output = input.run(
+                                &mut handle_frame,
+                                audio.as_ref(),
+                                &mut pixels,
+                                &mut layers,
+                            );
+                            if result_sender.send((output, pixels, layers)).is_err() {
+                                break;
+                            }
+                        }
+                    });
+                    FrameHandler::Threaded(UpdateThread {
+                        jobs: job_sender,
+                        results,
+                        back_buffer: Some((Vec::new(), Vec::new())),
+                    })
+                }
+                FrameCallback::Inline(handle_frame) => FrameHandler::Inline(handle_frame),
+            };
+
+            let mut runner = WindowRunner {
+                bounding_box: window.current_bounding_box(),
+                current_frame: 0,
+                last_frame_time: None,
+                fixed_update_accumulator: 0,
+                fps: 0.0,
+                fps_frame_count: 0,
+                fps_window_start: 0,
+                over_budget: false,
+                debug_overlay_font: None,
+                engine: self,
+                frame_handler,
+
+                // Windows start out focused
+                is_focused: true,
+
+                mouse_pos: (0.0, 0.0),
+                last_mouse_pos: (0.0, 0.0),
+                is_mouse_in_window: false,
+                was_mouse_in_window: false,
+                scroll_delta: (0.0, 0.0),
+                scroll_remainder: (0.0, 0.0),
+                mouse_button_states: HashMap::new(),
+                key_states: HashMap::new(),
+                physical_key_states: HashMap::new(),
+                repeated_keys: HashSet::new(),
+                input_events: Vec::new(),
+                key_event_frames: HashMap::new(),
+                physical_key_event_frames: HashMap::new(),
+
+                window_commands: Vec::new(),
+                cursor_icon: CursorIcon::Default,
+                screen_shake: ScreenShake::default(),
+
+                close_requested: false,
+            };
+            match drive {
+                None => window.run(target_fps, &*clock, &mut runner),
+                Some(drive) => {
+                    struct Stepper<'w, 'r, R> {
+                        window: &'w mut Window,
+                        timing: FrameTiming,
+                        clock: &'w dyn Clock,
+                        runner: &'r mut R,
+                        running: bool,
+                    }
+                    impl<R: WindowClient> StepDriver for Stepper<'_, '_, R> {
+                        fn poll_and_render(&mut self) -> bool {
+                            self.running = self.running
+                                && self
+                                    .window
+                                    .step(&mut self.timing, self.clock, self.runner)
+                                    .is_some();
+                            self.running
+                        }
+
+                        fn read_framebuffer(&mut self) -> Sprite {
+                            let (width, height, rgb) = self.window.read_framebuffer(
+                                self.runner.get_pixels(),
+                                self.runner.get_layers(),
+                            );
+                            let pixels = rgb
+                                .chunks_exact(3)
+                                .map(|pixel| [pixel[0], pixel[1], pixel[2]])
+                                .collect();
+                            Sprite::new(width, height, pixels)
+                        }
+                    }
 
-            is_focused: false,
+                    let mut stepper = Stepper {
+                        window: &mut window,
+                        timing: FrameTiming::new(target_fps, clock.now_nanos()),
+                        clock: &*clock,
+                        runner: &mut runner,
+                        running: true,
+                    };
+                    drive(&mut EngineStep {
+                        driver: &mut stepper,
+                    });
+                }
+            }
+        });
 
-            mouse_pos: (0.0, 0.0),
-            is_mouse_in_window: false,
-            mouse_button_states: HashMap::new(),
-            key_states: HashMap::new(),
+        self.shutdown();
+        drop(window);
+    }
 
-            will_exit: false,
-        });
+    /// Stops audio and closes the window, in that order so the audio stream is never running
+    /// while the window's GL context is torn down. This happens automatically when [`run`]
+    /// returns or the engine is dropped.
+    ///
+    /// [`run`]: Self::run
+    pub fn shutdown(&mut self) {
+        self.audio = None;
+        self.window = None;
     }
 
     // fn recalculate_gl(&mut self) {
@@ -356,24 +1656,85 @@ impl Engine {
     pub fn height(&self) -> u32 {
         self.height
     }
+    /// The GPU and driver the window is drawn with, read when the engine was built.
+    #[inline]
+    pub fn renderer_info(&self) -> &RendererInfo {
+        &self.renderer_info
+    }
+    /// The last frame shown in the window. Once [`Engine::run`] returns, this is the final
+    /// frame of the game.
+    ///
+    /// With [`Engine::run_threaded`], the frame being drawn when the game exits is
+    /// never shown, so this is the one before it.
+    #[inline]
+    pub fn pixels(&self) -> &[[u8; 3]] {
+        draw::rgb_pixels(&self.pixels)
+    }
+    /// The RGBA pixels of layer `index` in the last frame shown, like [`pixels`](Self::pixels)
+    /// is for layer 0. See [`EngineBuilder::layer_count`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is 0 or not less than the layer count.
+    #[inline]
+    pub fn layer(&self, index: usize) -> &[[u8; 4]] {
+        bytemuck::cast_slice(&self.layers[layer_index(index)].rgba)
+    }
+}
+
+impl Drop for Engine {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
 }
 
 pub struct Context<'a> {
     width: u32,
     height: u32,
+    window_size: (u32, u32),
+    bounding_box: BoundingBox,
     current_frame: u64,
+    monitor_refresh_rate: Option<u32>,
+    fixed_updates: u32,
+    render_alpha: f32,
+    fps: f32,
+    target_fps: u32,
+    over_budget: bool,
+    logical_size: Option<(u32, u32)>,
+    is_focused: bool,
 
     mouse_pos: (f32, f32),
+    mouse_delta: (f32, f32),
     is_mouse_in_window: bool,
+    was_mouse_in_window: bool,
+    is_mouse_captured: bool,
+    scroll_delta: (f32, f32),
+    scroll_ticks: (i32, i32),
 
     mouse_button_states: &'a HashMap<MouseButton, PressedState>,
 
     key_states: &'a HashMap<Key, PressedState>,
-
+    physical_key_states: &'a HashMap<Key, PressedState>,
+    repeated_keys: &'a HashSet<Key>,
+    input_events: &'a [InputEvent],
+
+    window_position: Option<(i32, i32)>,
+    window_opacity: f32,
+    window_commands: &'a mut Vec<WindowCommand>,
+    cursor_icon: CursorIcon,
+    debug_overlay: bool,
+    raw_frame: Option<Vec<u8>>,
+    layers: &'a mut [Layer],
+    screen_shake: ScreenShake,
+    added_screen_shakes: Vec<(f32, f32)>,
+    injected_events: Vec<InputEvent>,
+
+    close_requested: bool,
     will_exit: bool,
 }
 
 impl<'a> Context<'a> {
+    /// Whether the engine will stop after this frame.
     #[inline]
     pub fn will_exit(&self) -> bool {
         self.will_exit
@@ -382,8 +1743,41 @@ impl<'a> Context<'a> {
     pub fn exit(&mut self) {
         self.will_exit = true;
     }
+    /// Keeps running after this frame, also answering a pending close request like
+    /// [`cancel_exit`](Self::cancel_exit).
     #[inline]
     pub fn prevent_exit(&mut self) {
+        self.close_requested = false;
+        self.will_exit = false;
+    }
+    /// Whether the window was asked to close, such as by its close button, and the game hasn't
+    /// answered yet.
+    ///
+    /// Closing goes through these states:
+    ///
+    /// - While running, a close request makes this true.
+    /// - The game answers with [`confirm_exit`](Self::confirm_exit), which stops the engine after
+    ///   the frame, or [`cancel_exit`](Self::cancel_exit), which goes back to running.
+    /// - Until then, with [`EngineBuilder::confirm_exit`] on, this stays true every frame while
+    ///   the game keeps running and drawing, so it can show a confirmation dialog. Otherwise an
+    ///   unanswered request exits after the frame it arrived in.
+    ///
+    /// Closing the window again while a request is pending doesn't start a new one.
+    #[inline]
+    pub fn is_close_requested(&self) -> bool {
+        self.close_requested
+    }
+    /// Answers a close request by exiting after this frame. Same as [`exit`](Self::exit) if
+    /// there's no request.
+    #[inline]
+    pub fn confirm_exit(&mut self) {
+        self.close_requested = false;
+        self.will_exit = true;
+    }
+    /// Answers a close request by continuing to run.
+    #[inline]
+    pub fn cancel_exit(&mut self) {
+        self.close_requested = false;
         self.will_exit = false;
     }
 
@@ -404,6 +1798,255 @@ impl<'a> Context<'a> {
         (self.width, self.height)
     }
     #[inline]
+    pub fn aspect_ratio(&self) -> f32 {
+        self.width as f32 / self.height as f32
+    }
+    /// The part of the game area that's guaranteed to be on screen. In fullscreen, the game area
+    /// can be slightly larger than the screen so that pixels stay evenly sized, and the pixels
+    /// around its edges may be cut off; this is the area without them. Otherwise it's the whole
+    /// game area.
+    pub fn safe_area(&self) -> Rect {
+        // The bounding box is in normalized device coordinates, where the screen spans -1 to 1
+        let visible_range = |min: f32, max: f32, size: u32| {
+            let to_pixel = |ndc: f32| (ndc - min) / (max - min) * size as f32;
+            let start = to_pixel(-1.0).ceil().max(0.0) as u32;
+            let end = (to_pixel(1.0).floor() as u32).min(size);
+            (start, end.saturating_sub(start))
+        };
+        let bounding_box = self.bounding_box;
+        let (x, width) = visible_range(bounding_box.min_x, bounding_box.max_x, self.width);
+        let (y, height) = visible_range(bounding_box.min_y, bounding_box.max_y, self.height);
+        Rect::new(x as i32, y as i32, width, height)
+    }
+    /// How many screen pixels wide each game pixel is drawn. Usually a whole number, but can be
+    /// fractional if the window was resized to a size that doesn't fit the game evenly.
+    ///
+    /// Useful for drawing things at the screen's resolution instead of the game's, like crisp
+    /// text on top of the game.
+    ///
+    /// With [`EngineBuilder::pixel_aspect_ratio`], pixels are drawn this wide but not this tall.
+    #[inline]
+    pub fn pixel_scale(&self) -> f32 {
+        let bounding_box = self.bounding_box;
+        // The bounding box spans 2 units for the whole window
+        (bounding_box.max_x - bounding_box.min_x) * 0.5 * self.window_size.0 as f32
+            / self.width as f32
+    }
+    /// The number of frames run over the last second.
+    #[inline]
+    pub fn fps(&self) -> f32 {
+        self.fps
+    }
+    /// The time each frame is meant to take, in seconds.
+    #[inline]
+    pub fn frame_duration(&self) -> f32 {
+        1.0 / self.target_fps as f32
+    }
+    /// Whether the previous frame's callback took longer than
+    /// [`frame_duration`](Self::frame_duration) to update and draw, meaning the game is running
+    /// behind. Games can check this to cut back on work, like spawning fewer particles, on slow
+    /// hardware.
+    ///
+    /// Only the callback is timed. Uploading the frame to the GPU and presenting it aren't
+    /// included.
+    #[inline]
+    pub fn over_budget(&self) -> bool {
+        self.over_budget
+    }
+    /// How many fixed simulation steps to run this frame. Always 1 unless
+    /// [`EngineBuilder::fixed_update_hz`] is set, in which case it can be 0 or several depending
+    /// on how much time passed since the last frame.
+    #[inline]
+    pub fn fixed_updates(&self) -> u32 {
+        self.fixed_updates
+    }
+    /// How far between the previous and latest fixed simulation steps this frame is, from 0 to 1.
+    /// Draw with `previous + (latest - previous) * render_alpha()` for smooth motion. Always 1
+    /// unless [`EngineBuilder::fixed_update_hz`] is set.
+    #[inline]
+    pub fn render_alpha(&self) -> f32 {
+        self.render_alpha
+    }
+    /// Turns the debug overlay on or off, taking effect when this frame is drawn. See
+    /// [`EngineBuilder::debug_overlay`].
+    #[inline]
+    pub fn toggle_debug_overlay(&mut self) {
+        self.debug_overlay = !self.debug_overlay;
+    }
+    /// Shows `icon` as the cursor while it's over the window. This only lasts for the current
+    /// frame; the cursor goes back to [`CursorIcon::Default`] once the game stops setting it, so
+    /// hover effects can set it every frame they apply.
+    #[inline]
+    pub fn set_cursor_icon(&mut self, icon: CursorIcon) {
+        self.cursor_icon = icon;
+    }
+    /// Sets the image shown by [`CursorIcon::Custom`]. `rgba` holds 4 bytes per pixel, rows top
+    /// to bottom, like [`Icon`].
+    ///
+    /// The hotspot `(hot_x, hot_y)` is the pixel of the image, counted from its top-left corner,
+    /// that sits exactly at the mouse position and is where clicks land. Setting a new image
+    /// replaces and frees the previous one. This uploads the image to the system, so call it once
+    /// rather than every frame; then show it with `set_cursor_icon(CursorIcon::Custom)`.
+    pub fn set_custom_cursor(
+        &mut self,
+        rgba: &[u8],
+        width: u32,
+        height: u32,
+        hot_x: u32,
+        hot_y: u32,
+    ) {
+        assert!((width * height * 4) as usize == rgba.len());
+        assert!(hot_x < width && hot_y < height);
+        self.window_commands.push(WindowCommand::CustomCursor {
+            width,
+            height,
+            rgba: rgba.to_vec(),
+            hot_x,
+            hot_y,
+        });
+    }
+    /// Adjusts the colors of the window's contents without changing the pixel buffer, like a
+    /// monitor's settings. The adjustments are applied on the GPU, so they cost nothing per
+    /// pixel, and stay in effect until they're set again.
+    ///
+    /// `gamma` is applied first, brightening midtones when above 1 and darkening them when below.
+    /// Then `contrast` scales colors away from mid-gray and `brightness` is added to them, with
+    /// colors ranging from 0 to 1. `set_display_adjustments(0.0, 1.0, 1.0)` restores the original
+    /// colors.
+    pub fn set_display_adjustments(&mut self, brightness: f32, contrast: f32, gamma: f32) {
+        assert!(gamma > 0.0);
+        self.window_commands
+            .push(WindowCommand::DisplayAdjustments {
+                brightness,
+                contrast,
+                gamma,
+            });
+    }
+    /// Shows `rgb` as this frame instead of what the game drew, for playing back frames decoded
+    /// from a video or animation. `rgb` holds 3 bytes per pixel, rows top to bottom, and has to
+    /// match the current dimensions exactly.
+    ///
+    /// The frame is copied right away but only shown once the frame callback returns, at the
+    /// usual frame rate, so a loop that decodes and presents one frame per callback plays back
+    /// at the target FPS.
+    pub fn present_raw(&mut self, rgb: &[u8]) -> Result<(), Box<dyn Error>> {
+        if rgb.len() != (self.width * self.height) as usize * 3 {
+            return Err(Box::new(StrError::new(
+                "frame size doesn't match the window dimensions",
+            )));
+        }
+        self.raw_frame = Some(rgb.to_vec());
+        Ok(())
+    }
+    /// Wraps the frame's pixel buffer in a [`Canvas`] that knows the current dimensions and
+    /// applies the current screen shake.
+    #[inline]
+    pub fn draw<'p>(&self, pixels: &'p mut [[u8; 3]]) -> Canvas<'p> {
+        let (x, y) = self.screen_shake.offset(self.current_frame);
+        let canvas = Canvas::new(pixels, self.width, self.height).with_shake_offset(x, y);
+        match self.logical_size {
+            Some((width, height)) => canvas.with_logical_size(width, height),
+            None => canvas,
+        }
+    }
+    /// The RGBA pixel buffer of layer `index`, drawn over layer 0, the frame callback's buffer,
+    /// and any layers with lower indices. Layers keep their contents between frames, like the
+    /// main buffer. See [`EngineBuilder::layer_count`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is 0 or not less than the layer count.
+    #[inline]
+    pub fn layer(&mut self, index: usize) -> &mut [[u8; 4]] {
+        bytemuck::cast_slice_mut(&mut self.layers[layer_index(index)].rgba)
+    }
+    /// Moves layer `index` by `(x, y)` game pixels when it's drawn, for scrolling it without
+    /// redrawing it. Whatever is moved outside the game's area is cut off. The offset stays
+    /// until it's set again.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is 0 or not less than the layer count.
+    #[inline]
+    pub fn set_layer_offset(&mut self, index: usize, x: i32, y: i32) {
+        self.layers[layer_index(index)].offset = (x, y);
+    }
+    /// How far layer `index` is moved, as set by [`set_layer_offset`](Self::set_layer_offset).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is 0 or not less than the layer count.
+    #[inline]
+    pub fn layer_offset(&self, index: usize) -> (i32, i32) {
+        self.layers[layer_index(index)].offset
+    }
+    /// Shakes everything drawn through [`Context::draw`] by up to `intensity` pixels in each
+    /// direction, fading out smoothly over `duration` seconds. Shakes stack: calling this while
+    /// the screen is already shaking adds to the current intensity.
+    ///
+    /// The shake is added on top of the canvas's camera, see [`Canvas::set_camera`]. It takes
+    /// effect immediately for canvases created afterwards.
+    pub fn add_screen_shake(&mut self, intensity: f32, duration: f32) {
+        self.screen_shake.add(intensity, duration);
+        self.added_screen_shakes.push((intensity, duration));
+    }
+    /// The offset the screen is currently shaken by, in pixels.
+    #[inline]
+    pub fn screen_shake_offset(&self) -> (i32, i32) {
+        self.screen_shake.offset(self.current_frame)
+    }
+    /// The position of the window's top-left corner in screen coordinates, or `None` in
+    /// fullscreen, borderless or not. Follows the window as the user drags it.
+    #[inline]
+    pub fn window_position(&self) -> Option<(i32, i32)> {
+        self.window_position
+    }
+    /// Moves the window's top-left corner to `(x, y)` in screen coordinates after this frame.
+    /// Does nothing in fullscreen, borderless or not.
+    pub fn set_window_position(&mut self, x: i32, y: i32) {
+        if self.window_position.is_some() {
+            self.window_position = Some((x, y));
+            self.window_commands.push(WindowCommand::Position { x, y });
+        }
+    }
+    /// How opaque the window is, from 0 for invisible to 1 for fully opaque.
+    #[inline]
+    pub fn window_opacity(&self) -> f32 {
+        self.window_opacity
+    }
+    /// Makes the whole window, including its border, partly see-through after this frame, for
+    /// fading the window in and out or overlays. `opacity` is clamped to `[0, 1]`. Does nothing on
+    /// platforms that don't support transparent windows.
+    pub fn set_window_opacity(&mut self, opacity: f32) {
+        let opacity = opacity.clamp(0.0, 1.0);
+        if opacity != self.window_opacity {
+            self.window_opacity = opacity;
+            self.window_commands.push(WindowCommand::Opacity(opacity));
+        }
+    }
+    /// Whether the window has keyboard focus. A game in the background can use this to pause or
+    /// to decide whether to [`request_attention`](Self::request_attention).
+    #[inline]
+    pub fn is_focused(&self) -> bool {
+        self.is_focused
+    }
+    /// Flashes the window's taskbar or dock icon after this frame until the user switches to it,
+    /// for when a game in the background needs the user, like when it's their turn. Does nothing
+    /// if the window is already focused or on platforms that don't support it.
+    pub fn request_attention(&mut self) {
+        self.window_commands.push(WindowCommand::RequestAttention);
+    }
+    /// Changes how frames are synchronized with the monitor after this frame.
+    pub fn set_swap_interval(&mut self, interval: SwapInterval) {
+        self.window_commands
+            .push(WindowCommand::SwapInterval(interval));
+    }
+    /// The refresh rate of the primary monitor in hertz, if known.
+    #[inline]
+    pub fn monitor_refresh_rate(&self) -> Option<u32> {
+        self.monitor_refresh_rate
+    }
+    #[inline]
     pub fn mouse_x(&self) -> f32 {
         self.mouse_pos.0
     }
@@ -415,14 +2058,83 @@ impl<'a> Context<'a> {
     pub fn mouse_pos(&self) -> (f32, f32) {
         self.mouse_pos
     }
+    /// Moves the mouse cursor to `(x, y)` in game pixels after this frame, for games that
+    /// recenter or snap the cursor. It can be placed over the letterbox too.
+    pub fn set_mouse_position(&mut self, x: f32, y: f32) {
+        self.mouse_pos = (x, y);
+        self.window_commands
+            .push(WindowCommand::CursorPosition { x, y });
+    }
+    /// How far the mouse moved since the last frame, in game pixels, as `(x, y)`. This is
+    /// `(0, 0)` on the frame the mouse enters the window and while it's outside, so moving
+    /// back in doesn't look like a big jump.
+    #[inline]
+    pub fn mouse_delta(&self) -> (f32, f32) {
+        self.mouse_delta
+    }
+    /// How far the mouse wheel or trackpad scrolled since the last frame, as `(x, y)`. Positive
+    /// `y` scrolls up and positive `x` scrolls right.
+    ///
+    /// Trackpads and some mice scroll by fractions of a step; see
+    /// [`scroll_ticks`](Self::scroll_ticks) for whole steps.
+    #[inline]
+    pub fn scroll_delta(&self) -> (f32, f32) {
+        self.scroll_delta
+    }
+    /// Whole scroll steps since the last frame, as `(x, y)`, for things like moving through a
+    /// menu. Fractional scrolling is added up across frames until it reaches a full step.
+    #[inline]
+    pub fn scroll_ticks(&self) -> (i32, i32) {
+        self.scroll_ticks
+    }
+    /// The size set with [`EngineBuilder::logical_size`], if any.
+    #[inline]
+    pub fn logical_size(&self) -> Option<(u32, u32)> {
+        self.logical_size
+    }
+    /// The mouse position in the space set with [`EngineBuilder::logical_size`]. The same as
+    /// [`mouse_pos`](Self::mouse_pos) if there isn't one.
+    #[inline]
+    pub fn logical_mouse_pos(&self) -> (f32, f32) {
+        to_logical(self.mouse_pos, (self.width, self.height), self.logical_size)
+    }
+    /// The pixel under the cursor. Rounds down, so positions left of or above the game area are
+    /// negative rather than snapping to 0.
     #[inline]
     pub fn integer_mouse_pos(&self) -> (i32, i32) {
-        (self.mouse_pos.0 as i32, self.mouse_pos.1 as i32)
+        mouse_pixel(self.mouse_pos)
+    }
+    /// The pixel under the cursor, clamped to the game area. When the cursor is over the
+    /// letterbox bars or outside the window, this is the closest pixel on the game area's edge.
+    #[inline]
+    pub fn clamped_mouse_pos(&self) -> (u32, u32) {
+        let (mouse_x, mouse_y) = self.integer_mouse_pos();
+        (
+            mouse_x.clamp(0, self.width as i32 - 1) as u32,
+            mouse_y.clamp(0, self.height as i32 - 1) as u32,
+        )
     }
+    /// Whether the cursor is over the window. Stays true while the mouse is captured; see
+    /// [`is_mouse_captured`](Self::is_mouse_captured).
     #[inline]
     pub fn is_mouse_in_window(&self) -> bool {
         self.is_mouse_in_window
     }
+    /// Whether a mouse button is held down or was released this frame. While captured, the mouse
+    /// position keeps updating and button releases are still reported even if the cursor is
+    /// dragged outside the window.
+    #[inline]
+    pub fn is_mouse_captured(&self) -> bool {
+        self.is_mouse_captured
+    }
+    #[inline]
+    pub fn mouse_just_entered(&self) -> bool {
+        self.is_mouse_in_window && !self.was_mouse_in_window
+    }
+    #[inline]
+    pub fn mouse_just_left(&self) -> bool {
+        !self.is_mouse_in_window && self.was_mouse_in_window
+    }
     #[inline]
     pub fn is_mouse_in_game_area(&self) -> bool {
         if !self.is_mouse_in_window() {
@@ -431,37 +2143,168 @@ impl<'a> Context<'a> {
         let (mouse_x, mouse_y) = self.integer_mouse_pos();
         mouse_x >= 0 && mouse_x < self.width as i32 && mouse_y >= 0 && mouse_y < self.height as i32
     }
+    /// Sets the pixel under the cursor to `color`, if the cursor is over the game area.
+    pub fn draw_at_mouse(&self, pixels: &mut [[u8; 3]], color: [u8; 3]) {
+        if self.is_mouse_in_game_area() {
+            let (mouse_x, mouse_y) = self.integer_mouse_pos();
+            draw::set_pixel(pixels, self.width, self.height, mouse_x, mouse_y, color);
+        }
+    }
+
+    /// The keys and mouse buttons held this frame, and the mouse position.
+    pub fn input_snapshot(&self) -> InputSnapshot {
+        let is_held = |state: &PressedState| *state != PressedState::JustReleased;
+        InputSnapshot {
+            keys: self
+                .key_states
+                .iter()
+                .filter(|(_, state)| is_held(state))
+                .map(|(&key, _)| key)
+                .collect(),
+            mouse_buttons: self
+                .mouse_button_states
+                .iter()
+                .filter(|(_, state)| is_held(state))
+                .map(|(&button, _)| button)
+                .collect(),
+            mouse_pos: self.mouse_pos,
+        }
+    }
 
     pub fn is_key_pressed(&self, key_code: Key) -> bool {
         self.key_states
             .get(&key_code)
-            .map_or(false, |state| *state != PressedState::JustReleased)
+            .is_some_and(|state| *state != PressedState::JustReleased)
     }
     pub fn is_key_just_pressed(&self, key_code: Key) -> bool {
         self.key_states
             .get(&key_code)
-            .map_or(false, |state| *state == PressedState::JustPressed)
+            .is_some_and(|state| *state == PressedState::JustPressed)
+    }
+    /// Whether any key is held.
+    pub fn is_any_key_pressed(&self) -> bool {
+        self.key_states
+            .values()
+            .any(|state| *state != PressedState::JustReleased)
+    }
+    /// A key that was pressed this frame, for "press any key" screens or capturing a key to
+    /// rebind a control to. If several were pressed at once, the one that comes first in [`Key`]
+    /// is returned.
+    pub fn any_key_just_pressed(&self) -> Option<Key> {
+        self.key_states
+            .iter()
+            .filter(|(_, state)| **state == PressedState::JustPressed)
+            .map(|(&key, _)| key)
+            .min()
     }
     pub fn is_key_just_released(&self, key_code: Key) -> bool {
         self.key_states
             .get(&key_code)
-            .map_or(false, |state| *state == PressedState::JustReleased)
+            .is_some_and(|state| *state == PressedState::JustReleased)
+    }
+    /// Like [`is_key_pressed`](Self::is_key_pressed), but for the key at a position on the
+    /// keyboard regardless of layout. See [`PhysicalKey`].
+    pub fn is_physical_key_pressed(&self, key: PhysicalKey) -> bool {
+        self.physical_key_states
+            .get(&key.0)
+            .is_some_and(|state| *state != PressedState::JustReleased)
+    }
+    pub fn is_physical_key_just_pressed(&self, key: PhysicalKey) -> bool {
+        self.physical_key_states
+            .get(&key.0)
+            .is_some_and(|state| *state == PressedState::JustPressed)
+    }
+    pub fn is_physical_key_just_released(&self, key: PhysicalKey) -> bool {
+        self.physical_key_states
+            .get(&key.0)
+            .is_some_and(|state| *state == PressedState::JustReleased)
+    }
+    /// Whether the operating system auto-repeated `key_code` this frame because it's being held.
+    /// The initial press isn't a repeat. Always false unless [`EngineBuilder::key_repeat`] is on.
+    pub fn is_key_repeated(&self, key_code: Key) -> bool {
+        self.repeated_keys.contains(&key_code)
+    }
+    /// Every key, mouse button, scroll and typed character event since the last frame, in the
+    /// order they happened. Useful when order matters, like typing text or handling two clicks
+    /// in one frame; the state queries such as [`is_key_pressed`](Self::is_key_pressed) still
+    /// work as usual alongside them.
+    ///
+    /// Key repeats are only included if [`EngineBuilder::key_repeat`] is on.
+    #[inline]
+    pub fn input_events(&self) -> &[InputEvent] {
+        self.input_events
+    }
+    /// Feeds `event` to the engine as if it came from the operating system. It takes effect
+    /// next frame, so injecting a key press makes [`is_key_just_pressed`](Self::is_key_just_pressed)
+    /// true then. Meant for scripted demos, tutorials and automated tests.
+    ///
+    /// Injected events are handled before real ones that arrive during the same frame, so if
+    /// both change the same key or button, the real event wins.
+    pub fn inject_event(&mut self, event: InputEvent) {
+        self.injected_events.push(event);
+    }
+    /// Presses or releases `key` next frame, as if the player did. See
+    /// [`inject_event`](Self::inject_event).
+    pub fn inject_key(&mut self, key: Key, pressed: bool) {
+        self.inject_event(InputEvent::Key {
+            key,
+            pressed,
+            repeat: false,
+        });
     }
     #[inline]
     pub fn is_mouse_button_pressed(&self, mouse_button: MouseButton) -> bool {
         self.mouse_button_states
             .get(&mouse_button)
-            .map_or(false, |state| *state != PressedState::JustReleased)
+            .is_some_and(|state| *state != PressedState::JustReleased)
     }
     pub fn is_mouse_button_just_pressed(&self, mouse_button: MouseButton) -> bool {
         self.mouse_button_states
             .get(&mouse_button)
-            .map_or(false, |state| *state == PressedState::JustPressed)
+            .is_some_and(|state| *state == PressedState::JustPressed)
     }
     pub fn is_mouse_button_just_released(&self, mouse_button: MouseButton) -> bool {
         self.mouse_button_states
             .get(&mouse_button)
-            .map_or(false, |state| *state == PressedState::JustReleased)
+            .is_some_and(|state| *state == PressedState::JustReleased)
+    }
+}
+
+// The pixel containing `mouse_pos`. Flooring rather than truncating keeps positions just above or
+// left of the game area from landing on pixel 0
+fn mouse_pixel(mouse_pos: (f32, f32)) -> (i32, i32) {
+    (mouse_pos.0.floor() as i32, mouse_pos.1.floor() as i32)
+}
+
+// Scales `position` on a buffer of `size` into the space of `logical_size`, if there is one
+fn to_logical(
+    position: (f32, f32),
+    size: (u32, u32),
+    logical_size: Option<(u32, u32)>,
+) -> (f32, f32) {
+    match logical_size {
+        Some((width, height)) => (
+            position.0 * width as f32 / size.0 as f32,
+            position.1 * height as f32 / size.1 as f32,
+        ),
+        None => position,
+    }
+}
+
+// Where layer `index` is in the list of layers above the first
+fn layer_index(index: usize) -> usize {
+    assert!(
+        index > 0,
+        "layer 0 is the frame callback's pixel buffer, not an RGBA layer"
+    );
+    index - 1
+}
+
+// Sets the number of extra layers and matches their buffers to the game's dimensions
+fn resize_layers(layers: &mut Vec<Layer>, count: usize, width: u32, height: u32) {
+    layers.resize_with(count, Layer::default);
+    for layer in layers {
+        layer.rgba.resize((width * height) as usize * 4, 0);
     }
 }
 
@@ -479,8 +2322,7 @@ fn calculate_fit_radii(
     );
     let scaled = (remaining_space.0 / width, remaining_space.1 / height);
     let fit_scale_fac = f32::min(scaled.0, scaled.1);
-    let radii = (width * fit_scale_fac, height * fit_scale_fac);
-    radii
+    (width * fit_scale_fac, height * fit_scale_fac)
 }
 
 pub(crate) fn get_window_size(
@@ -488,9 +2330,10 @@ pub(crate) fn get_window_size(
     height: u32,
     monitor_width: u32,
     monitor_height: u32,
+    pixel_aspect_ratio: f32,
 ) -> (f32, f32) {
     calculate_fit_radii(
-        width as f32,
+        width as f32 * pixel_aspect_ratio,
         height as f32,
         monitor_width as f32,
         monitor_height as f32,
@@ -520,3 +2363,51 @@ impl Debug for StrError {
 }
 
 impl Error for StrError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mouse_pixel_rounds_down_on_both_sides_of_zero() {
+        assert_eq!(mouse_pixel((-0.5, -0.5)), (-1, -1));
+        assert_eq!(mouse_pixel((-1.0, 0.0)), (-1, 0));
+        assert_eq!(mouse_pixel((0.5, 2.99)), (0, 2));
+        assert_eq!(mouse_pixel((-1.01, 3.0)), (-2, 3));
+    }
+
+    #[test]
+    fn pixel_aspect_ratio_widens_the_window() {
+        let square = get_window_size(16, 16, 1000, 1000, 1.0);
+        assert!((square.0 - square.1).abs() < 1e-3, "{square:?}");
+        let wide = get_window_size(16, 16, 1000, 1000, 2.0);
+        assert!((wide.0 - 2.0 * wide.1).abs() < 1e-3, "{wide:?}");
+    }
+
+    #[test]
+    #[should_panic(expected = "engine dimensions must be at least 1x1, got 0x0")]
+    fn build_rejects_unset_dimensions() {
+        EngineBuilder::default().build();
+    }
+
+    #[test]
+    #[should_panic(expected = "got 4x0")]
+    fn build_rejects_zero_height() {
+        EngineBuilder::default().dimensions(4, 0).build();
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn input_snapshot_round_trips_through_json() {
+        let snapshot = InputSnapshot {
+            keys: HashSet::from([Key::A, Key::Space, Key::Shift]),
+            mouse_buttons: HashSet::from([MouseButton::Right]),
+            mouse_pos: (12.5, -3.0),
+        };
+        let json = serde_json::to_string(&snapshot).unwrap();
+        assert_eq!(
+            serde_json::from_str::<InputSnapshot>(&json).unwrap(),
+            snapshot
+        );
+    }
+}
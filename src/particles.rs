@@ -0,0 +1,154 @@
+use crate::draw;
+
+/// A set of single-pixel particles that move under velocity and gravity until their lifetime
+/// runs out.
+#[derive(Default)]
+pub struct ParticleSystem {
+    positions: Vec<(f32, f32)>,
+    velocities: Vec<(f32, f32)>,
+    lifetimes: Vec<f32>,
+    colors: Vec<[u8; 3]>,
+
+    gravity: (f32, f32),
+    additive: bool,
+}
+
+impl ParticleSystem {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the acceleration applied to every particle, in pixels per second squared.
+    #[inline]
+    pub fn gravity(mut self, x: f32, y: f32) -> Self {
+        self.gravity = (x, y);
+        self
+    }
+
+    /// Adds particle colors to the pixels underneath instead of replacing them.
+    #[inline]
+    pub fn additive(mut self, additive: bool) -> Self {
+        self.additive = additive;
+        self
+    }
+
+    /// Adds a particle at `(x, y)` moving at `(vx, vy)` pixels per second that lives for
+    /// `lifetime` seconds.
+    pub fn spawn(&mut self, x: f32, y: f32, vx: f32, vy: f32, lifetime: f32, color: [u8; 3]) {
+        self.positions.push((x, y));
+        self.velocities.push((vx, vy));
+        self.lifetimes.push(lifetime);
+        self.colors.push(color);
+    }
+
+    /// Advances every particle by `dt` seconds and removes the ones that have expired.
+    pub fn update(&mut self, dt: f32) {
+        let mut i = 0;
+        while i < self.lifetimes.len() {
+            self.lifetimes[i] -= dt;
+            if self.lifetimes[i] <= 0.0 {
+                // Order doesn't matter, so fill the gap with the last particle
+                self.positions.swap_remove(i);
+                self.velocities.swap_remove(i);
+                self.lifetimes.swap_remove(i);
+                self.colors.swap_remove(i);
+                continue;
+            }
+
+            let velocity = &mut self.velocities[i];
+            velocity.0 += self.gravity.0 * dt;
+            velocity.1 += self.gravity.1 * dt;
+            let position = &mut self.positions[i];
+            position.0 += velocity.0 * dt;
+            position.1 += velocity.1 * dt;
+
+            i += 1;
+        }
+    }
+
+    /// Plots every live particle, skipping the ones outside the buffer.
+    pub fn draw(&self, pixels: &mut [[u8; 3]], width: u32, height: u32) {
+        for (&(x, y), &color) in self.positions.iter().zip(&self.colors) {
+            let (x, y) = (x.floor() as i32, y.floor() as i32);
+            if !self.additive {
+                draw::set_pixel(pixels, width, height, x, y, color);
+            } else if x >= 0 && x < width as i32 && y >= 0 && y < height as i32 {
+                let pixel = &mut pixels[(x + y * width as i32) as usize];
+                for (channel, add) in pixel.iter_mut().zip(color) {
+                    *channel = channel.saturating_add(add);
+                }
+            }
+        }
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.lifetimes.len()
+    }
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.lifetimes.is_empty()
+    }
+    pub fn clear(&mut self) {
+        self.positions.clear();
+        self.velocities.clear();
+        self.lifetimes.clear();
+        self.colors.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn particles_expire_after_their_lifetime() {
+        let mut particles = ParticleSystem::new();
+        particles.spawn(0.0, 0.0, 0.0, 0.0, 0.5, [255; 3]);
+        particles.spawn(0.0, 0.0, 0.0, 0.0, 1.5, [255; 3]);
+        particles.spawn(0.0, 0.0, 0.0, 0.0, 0.25, [255; 3]);
+        assert_eq!(particles.len(), 3);
+
+        particles.update(0.375);
+        assert_eq!(particles.len(), 2);
+        particles.update(0.375);
+        assert_eq!(particles.len(), 1);
+        particles.update(0.5);
+        assert_eq!(particles.len(), 1);
+        particles.update(0.5);
+        assert!(particles.is_empty());
+    }
+
+    #[test]
+    fn removing_a_particle_keeps_the_others_intact() {
+        let mut particles = ParticleSystem::new();
+        particles.spawn(0.0, 0.0, 0.0, 0.0, 0.5, [1; 3]);
+        particles.spawn(2.0, 1.0, 0.0, 0.0, 2.0, [2; 3]);
+        particles.spawn(3.0, 1.0, 0.0, 0.0, 2.0, [3; 3]);
+        particles.update(1.0);
+
+        let mut pixels = vec![[0; 3]; 4 * 2];
+        particles.draw(&mut pixels, 4, 2);
+        assert_eq!(&pixels[4..], [[0; 3], [0; 3], [2; 3], [3; 3]]);
+        assert!(pixels[..4].iter().all(|&pixel| pixel == [0; 3]));
+    }
+
+    #[test]
+    fn gravity_accelerates_particles() {
+        let mut particles = ParticleSystem::new().gravity(0.0, 8.0);
+        particles.spawn(0.5, 0.5, 2.0, 0.0, 10.0, [255; 3]);
+        particles.update(0.5);
+        // Velocity becomes (2, 4) before moving half a second
+        assert_eq!(particles.positions[0], (1.5, 2.5));
+    }
+
+    #[test]
+    fn additive_particles_add_to_the_pixels_underneath() {
+        let mut particles = ParticleSystem::new().additive(true);
+        particles.spawn(0.0, 0.0, 0.0, 0.0, 1.0, [100, 200, 0]);
+        particles.spawn(0.5, 0.5, 0.0, 0.0, 1.0, [100, 100, 5]);
+        let mut pixels = vec![[10; 3]];
+        particles.draw(&mut pixels, 1, 1);
+        assert_eq!(pixels, [[210, 255, 15]]);
+    }
+}
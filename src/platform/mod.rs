@@ -1,4 +1,4 @@
-use crate::{Key, MouseButton};
+use crate::{input::InputEvent, CursorIcon, Key, MouseButton, SwapInterval};
 
 pub trait WindowTrait: Sized {
     fn new(
@@ -7,47 +7,196 @@ pub trait WindowTrait: Sized {
         title: &str,
         icon: Option<crate::Icon>,
         fullscreen: bool,
+        borderless: bool,
+        display: DisplaySettings,
     ) -> Self;
 
     fn window_dimensions(&self) -> (u32, u32);
 
-    fn current_bounding_box(&self) -> (f32, f32, f32, f32);
+    fn monitor_refresh_rate(&self) -> Option<u32>;
 
-    fn run<T>(&mut self, client: &mut T)
+    fn renderer_info(&self) -> crate::RendererInfo;
+
+    fn window_position(&self) -> (i32, i32);
+
+    fn set_window_position(&mut self, x: i32, y: i32);
+
+    fn current_bounding_box(&self) -> BoundingBox;
+
+    /// Draws `pixels` and `layers` and reads back what ended up in the window, as its width,
+    /// height and RGB bytes with the top row first.
+    fn read_framebuffer(&mut self, pixels: &[u8], layers: &[Layer]) -> (u32, u32, Vec<u8>);
+
+    /// Handles pending events, runs any frames that are due and draws. Returns how many
+    /// nanoseconds are left until the next frame is due, or `None` once the client has stopped.
+    fn step<T>(
+        &mut self,
+        timing: &mut FrameTiming,
+        clock: &dyn crate::clock::Clock,
+        client: &mut T,
+    ) -> Option<u64>
     where
         T: WindowClient;
+
+    fn run<T>(&mut self, target_fps: u32, clock: &dyn crate::clock::Clock, client: &mut T)
+    where
+        T: WindowClient,
+    {
+        let mut timing = FrameTiming::new(target_fps, clock.now_nanos());
+        while let Some(wait_nanos) = self.step(&mut timing, clock, client) {
+            clock.sleep(wait_nanos);
+        }
+    }
+}
+
+/// How the game is drawn to the window, from the `EngineBuilder`. Kept by the window so drawing
+/// can be set up again if the GL context is lost.
+pub struct DisplaySettings {
+    pub post_process_shader: Option<String>,
+    /// Drawn as see-through, along with the letterbox.
+    pub transparent_color_key: Option<[u8; 3]>,
+    pub border_image: Option<crate::raster::Sprite>,
+    pub pixel_aspect_ratio: f32,
+    pub premultiplied_alpha: bool,
+}
+
+/// When the next frame is due, kept between calls to [`WindowTrait::step`].
+pub struct FrameTiming {
+    frame_nanos: u64,
+    next_frame_time: u64,
+}
+
+impl FrameTiming {
+    pub fn new(target_fps: u32, now_nanos: u64) -> Self {
+        let frame_nanos = 1_000_000_000 / target_fps as u64;
+        Self {
+            frame_nanos,
+            next_frame_time: now_nanos + frame_nanos,
+        }
+    }
+
+    /// Whether a frame is due at `now_nanos`, moving on to the one after it if so. Frames
+    /// missed while the loop was busy are all still due, so this keeps returning true until
+    /// they've been caught up.
+    pub fn take_due_frame(&mut self, now_nanos: u64) -> bool {
+        let is_due = now_nanos >= self.next_frame_time;
+        if is_due {
+            self.next_frame_time += self.frame_nanos;
+        }
+        is_due
+    }
+
+    /// How long from `now_nanos` until the next frame is due.
+    pub fn nanos_until_next_frame(&self, now_nanos: u64) -> u64 {
+        self.next_frame_time.saturating_sub(now_nanos)
+    }
+}
+
+/// Where the game is drawn within the window, in normalized device coordinates, where the
+/// window spans -1 to 1 on both axes.
+#[derive(PartialEq, Debug, Clone, Copy, Default)]
+pub(crate) struct BoundingBox {
+    pub(crate) min_x: f32,
+    pub(crate) min_y: f32,
+    pub(crate) max_x: f32,
+    pub(crate) max_y: f32,
+}
+
+impl BoundingBox {
+    /// A box centered in the window, reaching `radius_x` and `radius_y` from the center.
+    pub(crate) fn from_radii(radius_x: f32, radius_y: f32) -> Self {
+        Self {
+            min_x: -radius_x,
+            min_y: -radius_y,
+            max_x: radius_x,
+            max_y: radius_y,
+        }
+    }
+
+    // The box's top-left corner and size in window pixels. Boxes are always centered, so which
+    // way the y axis points doesn't matter
+    fn window_rect(&self, window_size: (u32, u32)) -> ((f32, f32), (f32, f32)) {
+        let half_window = (window_size.0 as f32 * 0.5, window_size.1 as f32 * 0.5);
+        (
+            (
+                self.min_x * half_window.0 + half_window.0,
+                self.min_y * half_window.1 + half_window.1,
+            ),
+            (
+                (self.max_x - self.min_x) * half_window.0,
+                (self.max_y - self.min_y) * half_window.1,
+            ),
+        )
+    }
+
+    /// Converts a position in window pixels to game pixels.
+    pub(crate) fn window_to_game(
+        &self,
+        x: f32,
+        y: f32,
+        window_size: (u32, u32),
+        game_size: (u32, u32),
+    ) -> (f32, f32) {
+        let (corner, size) = self.window_rect(window_size);
+        (
+            (x - corner.0) / size.0 * game_size.0 as f32,
+            (y - corner.1) / size.1 * game_size.1 as f32,
+        )
+    }
+
+    /// Converts a position in game pixels to window pixels. The inverse of
+    /// [`window_to_game`](Self::window_to_game).
+    pub(crate) fn game_to_window(
+        &self,
+        x: f32,
+        y: f32,
+        window_size: (u32, u32),
+        game_size: (u32, u32),
+    ) -> (f32, f32) {
+        let (corner, size) = self.window_rect(window_size);
+        (
+            x / game_size.0 as f32 * size.0 + corner.0,
+            y / game_size.1 as f32 * size.1 + corner.1,
+        )
+    }
 }
 
+/// Works out the game's dimensions and where it goes in the window. `pixel_aspect_ratio` is the
+/// width of a game pixel over its height, so the game takes up as much room as if it were that
+/// many times wider with square pixels.
 pub(crate) fn calculate_dimensions_and_bounding_box(
     target_width: u32,
     target_height: u32,
     window_width: u32,
     window_height: u32,
     fullscreen: bool,
-) -> ((u32, u32), (f32, f32, f32, f32)) {
+    pixel_aspect_ratio: f32,
+) -> ((u32, u32), BoundingBox) {
     let (window_width, window_height) = (window_width as f32, window_height as f32);
 
     if fullscreen {
+        // The height of a game pixel in window pixels. Its width is this times the aspect ratio
         let target_pixel_size = f32::min(
-            window_width / target_width as f32,
+            window_width / (target_width as f32 * pixel_aspect_ratio),
             window_height / target_height as f32,
         );
         let min_pixel_size = f32::max(
-            window_width / target_width as f32,
+            window_width / (target_width as f32 * pixel_aspect_ratio),
             window_height / target_height as f32,
         ) * 0.5;
         let pixel_size = f32::max(target_pixel_size, min_pixel_size);
+        let pixel_width = pixel_size * pixel_aspect_ratio;
 
-        let width = (window_width / pixel_size).ceil() as u32;
+        let width = (window_width / pixel_width).ceil() as u32;
         let height = (window_height / pixel_size).ceil() as u32;
         let radii = (
-            width as f32 * pixel_size / window_width,
+            width as f32 * pixel_width / window_width,
             height as f32 * pixel_size / window_height,
         );
-        ((width, height), (-radii.0, -radii.1, radii.0, radii.1))
+        ((width, height), BoundingBox::from_radii(radii.0, radii.1))
     } else {
         let window_radii = crate::calculate_fit_radii(
-            target_width as f32,
+            target_width as f32 * pixel_aspect_ratio,
             target_height as f32,
             window_width,
             window_height,
@@ -59,15 +208,55 @@ pub(crate) fn calculate_dimensions_and_bounding_box(
         );
         (
             (target_width, target_height),
-            (-radii.0, -radii.1, radii.0, radii.1),
+            BoundingBox::from_radii(radii.0, radii.1),
         )
     }
 }
 pub trait WindowClient: Sized {
     fn handle_event(&mut self, event: WindowEvent);
-    fn frame(&mut self, rand_source: u64) -> bool;
-    fn get_bounding_box(&self) -> (f32, f32, f32, f32);
+    fn frame(&mut self, time_nanos: u64) -> bool;
+    fn take_commands(&mut self) -> Vec<WindowCommand>;
     fn get_pixels(&self) -> &[u8];
+    fn get_layers(&self) -> &[Layer];
+}
+
+/// A layer drawn over the game's pixels, from `EngineBuilder::layer_count`.
+#[derive(Debug, Clone, Default)]
+pub struct Layer {
+    /// 4 bytes per pixel, the same size as the game's pixel buffer.
+    pub rgba: Vec<u8>,
+    /// How far the layer is moved, in game pixels.
+    pub offset: (i32, i32),
+}
+
+/// Requests from game code to the window, applied after each frame.
+#[derive(Debug)]
+pub enum WindowCommand {
+    Position {
+        x: i32,
+        y: i32,
+    },
+    /// In game pixels.
+    CursorPosition {
+        x: f32,
+        y: f32,
+    },
+    CursorIcon(CursorIcon),
+    Opacity(f32),
+    SwapInterval(SwapInterval),
+    CustomCursor {
+        width: u32,
+        height: u32,
+        rgba: Vec<u8>,
+        hot_x: u32,
+        hot_y: u32,
+    },
+    DisplayAdjustments {
+        brightness: f32,
+        contrast: f32,
+        gamma: f32,
+    },
+    RequestAttention,
 }
 
 #[derive(Debug)]
@@ -79,26 +268,186 @@ pub enum WindowEvent {
     Key {
         key: Key,
         pressed: bool,
+        repeat: bool,
+    },
+    // The key at the same position on a US layout as the key that was pressed
+    PhysicalKey {
+        key: Key,
+        pressed: bool,
     },
     MouseEnter {
         entered: bool,
     },
     MousePos {
-        x: u32,
-        y: u32,
+        x: i32,
+        y: i32,
     },
+    Scroll {
+        x: f32,
+        y: f32,
+    },
+    Char(char),
     FocusChanged {
         focused: bool,
     },
     WindowClose,
+    WindowMove {
+        x: i32,
+        y: i32,
+    },
     WindowResize {
         width: u32,
         height: u32,
         window_width: u32,
         window_height: u32,
-        new_bounding_box: (f32, f32, f32, f32),
+        new_bounding_box: BoundingBox,
     },
 }
 
+// For events injected with `Context::inject_event`
+impl From<InputEvent> for WindowEvent {
+    fn from(event: InputEvent) -> Self {
+        match event {
+            InputEvent::Key {
+                key,
+                pressed,
+                repeat,
+            } => Self::Key {
+                key,
+                pressed,
+                repeat,
+            },
+            InputEvent::MouseButton { button, pressed } => Self::MouseButton { button, pressed },
+            InputEvent::Scroll { x, y } => Self::Scroll { x, y },
+            InputEvent::Char(c) => Self::Char(c),
+        }
+    }
+}
+
 mod native;
+pub(crate) use native::display_effect_shader;
 pub type Window = native::GLFWWindow;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::{Clock, ManualClock};
+
+    const FRAME_NANOS: u64 = 1_000_000_000 / 50;
+
+    // Runs every frame that's due, like `WindowTrait::step`, and returns how many there were
+    fn run_due_frames(timing: &mut FrameTiming, clock: &ManualClock) -> u32 {
+        let mut frames = 0;
+        while timing.take_due_frame(clock.now_nanos()) {
+            frames += 1;
+        }
+        frames
+    }
+
+    #[test]
+    fn sleeping_until_the_next_frame_makes_it_due() {
+        let clock = ManualClock::new();
+        let mut timing = FrameTiming::new(50, clock.now_nanos());
+        assert_eq!(run_due_frames(&mut timing, &clock), 0);
+
+        for _ in 0..10 {
+            clock.sleep(timing.nanos_until_next_frame(clock.now_nanos()));
+            assert_eq!(run_due_frames(&mut timing, &clock), 1);
+        }
+        assert_eq!(clock.now_nanos(), FRAME_NANOS * 10);
+    }
+
+    #[test]
+    fn frames_missed_during_a_stall_are_caught_up() {
+        let clock = ManualClock::new();
+        let mut timing = FrameTiming::new(50, clock.now_nanos());
+        clock.advance(FRAME_NANOS * 3 + FRAME_NANOS / 2);
+        assert_eq!(run_due_frames(&mut timing, &clock), 3);
+        // The schedule isn't pushed back, so the next frame is still on the original beat
+        assert_eq!(
+            timing.nanos_until_next_frame(clock.now_nanos()),
+            FRAME_NANOS / 2
+        );
+    }
+
+    #[test]
+    fn a_frame_is_due_exactly_on_time() {
+        let clock = ManualClock::new();
+        let mut timing = FrameTiming::new(50, clock.now_nanos());
+        clock.advance(FRAME_NANOS - 1);
+        assert_eq!(run_due_frames(&mut timing, &clock), 0);
+        assert_eq!(timing.nanos_until_next_frame(clock.now_nanos()), 1);
+        clock.advance(1);
+        assert_eq!(run_due_frames(&mut timing, &clock), 1);
+    }
+
+    #[test]
+    fn window_to_game_maps_the_box_corners_to_the_game_corners() {
+        // A 100x50 box in the middle of a 200x100 window
+        let bounding_box = BoundingBox::from_radii(0.5, 0.5);
+        let to_game = |x, y| bounding_box.window_to_game(x, y, (200, 100), (20, 10));
+        assert_eq!(to_game(50.0, 25.0), (0.0, 0.0));
+        assert_eq!(to_game(150.0, 75.0), (20.0, 10.0));
+        assert_eq!(to_game(100.0, 50.0), (10.0, 5.0));
+        // The letterbox is outside the game
+        assert_eq!(to_game(0.0, 0.0), (-10.0, -5.0));
+    }
+
+    #[test]
+    fn game_to_window_inverts_window_to_game() {
+        let bounding_box = BoundingBox::from_radii(0.8, 1.0);
+        for (x, y) in [(0.0, 0.0), (3.5, 7.25), (16.0, 9.0), (-2.0, 11.0)] {
+            let (window_x, window_y) = bounding_box.game_to_window(x, y, (640, 360), (16, 9));
+            let (game_x, game_y) =
+                bounding_box.window_to_game(window_x, window_y, (640, 360), (16, 9));
+            assert!((game_x - x).abs() < 1e-4 && (game_y - y).abs() < 1e-4);
+        }
+        assert_eq!(
+            bounding_box.game_to_window(0.0, 0.0, (640, 360), (16, 9)),
+            (64.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn windowed_games_are_scaled_up_and_letterboxed() {
+        // A 2:1 game in a square window keeps its shape, with bars above and below
+        let (game_size, bounding_box) =
+            calculate_dimensions_and_bounding_box(32, 16, 128, 128, false, 1.0);
+        assert_eq!(game_size, (32, 16));
+        let top_left = bounding_box.game_to_window(0.0, 0.0, (128, 128), game_size);
+        let bottom_right = bounding_box.game_to_window(32.0, 16.0, (128, 128), game_size);
+        let size = (bottom_right.0 - top_left.0, bottom_right.1 - top_left.1);
+        assert!((size.0 - 2.0 * size.1).abs() < 1e-3, "{size:?}");
+        assert!(size.0 > 32.0 * 3.0, "{size:?}");
+        // Centered, with equal bars
+        assert!((top_left.0 + bottom_right.0 - 128.0).abs() < 1e-3);
+        assert!((top_left.1 + bottom_right.1 - 128.0).abs() < 1e-3);
+        assert!(top_left.1 > 32.0);
+    }
+
+    #[test]
+    fn fullscreen_games_grow_to_fill_the_screen() {
+        let (game_size, bounding_box) =
+            calculate_dimensions_and_bounding_box(32, 16, 128, 128, true, 1.0);
+        // Pixels stay 4x4 and the game gains rows instead of leaving bars
+        assert_eq!(game_size, (32, 32));
+        assert_eq!(bounding_box, BoundingBox::from_radii(1.0, 1.0));
+    }
+
+    #[test]
+    fn wide_pixels_double_the_effective_width() {
+        let (game_size, bounding_box) =
+            calculate_dimensions_and_bounding_box(16, 16, 128, 128, false, 2.0);
+        assert_eq!(game_size, (16, 16));
+        let top_left = bounding_box.game_to_window(0.0, 0.0, (128, 128), game_size);
+        let bottom_right = bounding_box.game_to_window(16.0, 16.0, (128, 128), game_size);
+        let size = (bottom_right.0 - top_left.0, bottom_right.1 - top_left.1);
+        assert!((size.0 - 2.0 * size.1).abs() < 1e-3, "{size:?}");
+
+        // Fullscreen pixels are 8x4, so a square screen fits twice as many rows as columns
+        let (game_size, bounding_box) =
+            calculate_dimensions_and_bounding_box(16, 16, 128, 128, true, 2.0);
+        assert_eq!(game_size, (16, 32));
+        assert_eq!(bounding_box, BoundingBox::from_radii(1.0, 1.0));
+    }
+}
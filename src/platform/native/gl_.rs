@@ -1,14 +1,106 @@
+use std::borrow::Cow;
+
+use crate::platform::{BoundingBox, DisplaySettings, Layer};
+use crate::raster::Sprite;
+use crate::RendererInfo;
+
+/// The kinds of OpenGL context the renderer can draw with, in the order they're tried.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum GlVersion {
+    Gl33,
+    // For devices that only have OpenGL ES, like the Raspberry Pi and some virtual machines
+    Gles30,
+    // For old drivers. Vertex array objects come from ARB_vertex_array_object, which almost
+    // every 2.1 driver has
+    Gl21,
+}
+
+impl GlVersion {
+    pub const FALLBACKS: [GlVersion; 3] = [Self::Gl33, Self::Gles30, Self::Gl21];
+
+    // The shaders are written for GLSL 3.30. This swaps their `#version` line for one this
+    // version understands, along with whatever else it needs to compile them
+    fn translate_shader(self, source: &str, shader_type: u32) -> Cow<'_, str> {
+        let body = source
+            .trim_start()
+            .strip_prefix("#version 330")
+            .unwrap_or(source);
+        match self {
+            Self::Gl33 => Cow::Borrowed(source),
+            // Fragment shaders have no default float precision in GLSL ES
+            Self::Gles30 => {
+                Cow::Owned(format!("#version 300 es\nprecision mediump float;\n{body}"))
+            }
+            Self::Gl21 => {
+                let header = if shader_type == gl::VERTEX_SHADER {
+                    "#define in attribute\n#define out varying\n"
+                } else {
+                    "#define in varying\n#define texture texture2D\n#define frag_color gl_FragColor\n"
+                };
+                // Outputs can't be declared in GLSL 1.20, and `frag_color` becomes the built-in
+                // output, so output declarations are left out
+                let body: String = body
+                    .lines()
+                    .filter(|line| {
+                        shader_type == gl::VERTEX_SHADER || !line.trim_start().starts_with("out ")
+                    })
+                    .flat_map(|line| [line, "\n"])
+                    .collect();
+                Cow::Owned(format!(
+                    "#version 120\n#define layout(location)\n#define lowp\n#define mediump\n#define highp\n{header}{body}"
+                ))
+            }
+        }
+    }
+}
+
 pub struct Gl {
     width: u32,
     height: u32,
+    pixel_aspect_ratio: f32,
+    premultiplied_alpha: bool,
+
+    bounding_box: BoundingBox,
+    window_size: (u32, u32),
 
-    bounding_box: (f32, f32, f32, f32),
+    renderer_info: RendererInfo,
 
     program: u32,
     vao: u32,
     pos_vbo: u32,
     uv_vbo: u32,
     texture: u32,
+    // One for each layer drawn so far, created as they're needed
+    layer_textures: Vec<u32>,
+
+    offset_location: i32,
+    brightness_location: i32,
+    contrast_location: i32,
+    gamma_location: i32,
+
+    border: Option<Border>,
+    post_process: Option<PostProcess>,
+}
+
+// An image stretched over the whole window behind the game, showing in the letterbox
+struct Border {
+    vao: u32,
+    vbo: u32,
+    texture: u32,
+}
+
+// A second pass that draws the game, rendered into `texture` through `framebuffer`, to the
+// window with a custom fragment shader
+struct PostProcess {
+    program: u32,
+    vao: u32,
+    vbo: u32,
+    framebuffer: u32,
+    texture: u32,
+
+    resolution_location: i32,
+    game_rect_location: i32,
+    game_size_location: i32,
 }
 
 macro_rules! gl_load {
@@ -20,76 +112,54 @@ macro_rules! gl_load {
 }
 
 impl Gl {
-    pub fn new<F>(width: u32, height: u32, mut loader_function: F) -> Self
+    pub fn new<F>(
+        version: GlVersion,
+        width: u32,
+        height: u32,
+        display: &DisplaySettings,
+        mut loader_function: F,
+    ) -> Self
     where
         F: FnMut(&'static str) -> *const std::ffi::c_void,
     {
         unsafe {
             gl_load!(
-                GetString CreateProgram CreateShader ShaderSource CompileShader AttachShader LinkProgram DetachShader DeleteShader UseProgram GenVertexArrays BindVertexArray GenBuffers BindBuffer EnableVertexAttribArray VertexAttribPointer GenTextures ActiveTexture BindTexture TexParameteri PixelStorei TexImage2D ClearColor Clear DrawArrays Viewport BufferData DeleteProgram DeleteVertexArrays DeleteBuffers DeleteTextures, loader_function);
+                GetString CreateProgram CreateShader ShaderSource CompileShader AttachShader LinkProgram DetachShader DeleteShader UseProgram GenVertexArrays BindVertexArray GenBuffers BindBuffer EnableVertexAttribArray VertexAttribPointer GenTextures ActiveTexture BindTexture TexParameteri PixelStorei TexImage2D ClearColor Clear DrawArrays Viewport BufferData DeleteProgram DeleteVertexArrays DeleteBuffers DeleteTextures GetUniformLocation Uniform1i Uniform1f Uniform2f Uniform3f Uniform4f GenFramebuffers BindFramebuffer FramebufferTexture2D DeleteFramebuffers GetGraphicsResetStatus BindAttribLocation ReadPixels Enable Disable BlendFuncSeparate Scissor, loader_function);
             #[cfg(debug_assertions)]
             gl_load!(GetProgramiv GetShaderiv GetError, loader_function);
 
-            // let version = std::ffi::CStr::from_ptr(gl::GetString(gl::VERSION) as *const _)
-            //     .to_str()
-            //     .unwrap();
-
-            let program = gl::CreateProgram();
-
-            unsafe fn compile_shader(program: u32, source: &str, shader_type: u32) -> u32 {
-                let shader = gl::CreateShader(shader_type);
-                gl::ShaderSource(
-                    shader,
-                    1,
-                    &(source.as_bytes().as_ptr() as *const _),
-                    &(source.len() as i32),
-                );
-                gl::CompileShader(shader);
-
-                #[cfg(debug_assertions)]
-                {
-                    let mut status = 0;
-                    gl::GetShaderiv(shader, gl::COMPILE_STATUS, &mut status);
-
-                    if status != 1 {
-                        panic!("shader compilation error");
-                    }
-                }
-
-                gl::AttachShader(program, shader);
-
-                shader
-            }
+            let renderer_info = RendererInfo {
+                vendor: gl_string(gl::VENDOR),
+                renderer: gl_string(gl::RENDERER),
+                version: gl_string(gl::VERSION),
+            };
 
-            let vertex_shader =
-                compile_shader(program, include_str!("shader/vert.glsl"), gl::VERTEX_SHADER);
-            let fragment_shader = compile_shader(
-                program,
+            let program = create_program(
+                version,
+                include_str!("shader/vert.glsl"),
                 include_str!("shader/frag.glsl"),
-                gl::FRAGMENT_SHADER,
             );
 
-            gl::LinkProgram(program);
-
-            #[cfg(debug_assertions)]
-            {
-                let mut status = 0;
-                gl::GetProgramiv(program, gl::LINK_STATUS, &mut status);
-                if status != 1 {
-                    panic!("program error");
-                }
-            }
+            gl::UseProgram(program);
 
-            unsafe fn delete_shader(program: u32, shader: u32) {
-                gl::DetachShader(program, shader);
-                gl::DeleteShader(shader);
+            let offset_location = gl::GetUniformLocation(program, c"offset".as_ptr());
+            let brightness_location = gl::GetUniformLocation(program, c"brightness".as_ptr());
+            let contrast_location = gl::GetUniformLocation(program, c"contrast".as_ptr());
+            let gamma_location = gl::GetUniformLocation(program, c"gamma".as_ptr());
+            if let Some(color_key) = display.transparent_color_key {
+                let [r, g, b] = color_key.map(|channel| channel as f32 / 255.0);
+                gl::Uniform3f(
+                    gl::GetUniformLocation(program, c"color_key".as_ptr()),
+                    r,
+                    g,
+                    b,
+                );
+                gl::Uniform1i(
+                    gl::GetUniformLocation(program, c"use_color_key".as_ptr()),
+                    1,
+                );
             }
 
-            delete_shader(program, vertex_shader);
-            delete_shader(program, fragment_shader);
-
-            gl::UseProgram(program);
-
             let mut vao = 0;
             gl::GenVertexArrays(1, &mut vao);
             gl::BindVertexArray(vao);
@@ -118,16 +188,15 @@ impl Gl {
             gl::GenTextures(1, &mut texture);
             gl::ActiveTexture(gl::TEXTURE0);
             gl::BindTexture(gl::TEXTURE_2D, texture);
-            gl::TexParameteri(
-                gl::TEXTURE_2D,
-                gl::TEXTURE_WRAP_S,
-                gl::CLAMP_TO_BORDER as i32,
-            );
-            gl::TexParameteri(
-                gl::TEXTURE_2D,
-                gl::TEXTURE_WRAP_T,
-                gl::CLAMP_TO_BORDER as i32,
-            );
+            // OpenGL ES 3.0 doesn't have border clamping. The texture is only sampled inside its
+            // edges anyway
+            let wrap = if version == GlVersion::Gles30 {
+                gl::CLAMP_TO_EDGE
+            } else {
+                gl::CLAMP_TO_BORDER
+            };
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, wrap as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, wrap as i32);
             gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as i32);
             gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST as i32);
             gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
@@ -143,21 +212,58 @@ impl Gl {
                 std::ptr::null(),
             );
 
-            gl::ClearColor(0.0, 0.0, 0.0, 1.0);
+            // With a color key the window is transparent, and so are the letterbox bars
+            let clear_alpha = if display.transparent_color_key.is_some() {
+                0.0
+            } else {
+                1.0
+            };
+            gl::ClearColor(0.0, 0.0, 0.0, clear_alpha);
+
+            let border = display.border_image.as_ref().map(|image| {
+                let border = Border::new(image);
+                gl::BindVertexArray(vao);
+                gl::BindTexture(gl::TEXTURE_2D, texture);
+                border
+            });
+
+            let post_process = display.post_process_shader.as_deref().map(|source| {
+                let post_process = PostProcess::new(version, source);
+                gl::UseProgram(program);
+                gl::BindVertexArray(vao);
+                gl::BindTexture(gl::TEXTURE_2D, texture);
+                post_process
+            });
 
             let obj = Self {
                 width,
                 height,
+                pixel_aspect_ratio: display.pixel_aspect_ratio,
+                premultiplied_alpha: display.premultiplied_alpha,
+
+                bounding_box: BoundingBox::default(),
+                window_size: (0, 0),
 
-                bounding_box: (0.0, 0.0, 0.0, 0.0),
+                renderer_info,
 
                 program,
                 vao,
                 pos_vbo,
                 uv_vbo,
                 texture,
+                layer_textures: Vec::new(),
+
+                offset_location,
+                brightness_location,
+                contrast_location,
+                gamma_location,
+
+                border,
+                post_process,
             };
 
+            obj.set_display_adjustments(0.0, 1.0, 1.0);
+
             obj.check_for_gl_error();
 
             obj
@@ -169,18 +275,34 @@ impl Gl {
         #[cfg(debug_assertions)]
         {
             let err = gl::GetError();
-            if err != gl::NO_ERROR {
+            // A lost context is handled by the window after the frame is presented
+            if err != gl::NO_ERROR && err != gl::CONTEXT_LOST {
                 panic!("opengl error {err:#02x}");
             }
         }
     }
 
-    pub fn draw(&mut self, pixels: &[u8]) {
+    pub fn draw(&mut self, pixels: &[u8], layers: &[Layer]) {
         debug_assert_eq!(pixels.len(), (self.width * self.height) as usize * 3);
 
         unsafe {
+            if let Some(post_process) = &self.post_process {
+                gl::BindFramebuffer(gl::FRAMEBUFFER, post_process.framebuffer);
+                gl::UseProgram(self.program);
+                gl::BindVertexArray(self.vao);
+                gl::BindTexture(gl::TEXTURE_2D, self.texture);
+            }
+
             gl::Clear(gl::COLOR_BUFFER_BIT);
 
+            if let Some(border) = &self.border {
+                gl::BindVertexArray(border.vao);
+                gl::BindTexture(gl::TEXTURE_2D, border.texture);
+                gl::DrawArrays(gl::TRIANGLE_STRIP, 0, 4);
+                gl::BindVertexArray(self.vao);
+                gl::BindTexture(gl::TEXTURE_2D, self.texture);
+            }
+
             gl::TexImage2D(
                 gl::TEXTURE_2D,
                 0,
@@ -195,8 +317,144 @@ impl Gl {
 
             gl::DrawArrays(gl::TRIANGLE_STRIP, 0, 4);
 
+            if !layers.is_empty() {
+                self.draw_layers(layers);
+            }
+
+            if let Some(post_process) = &self.post_process {
+                gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+                gl::UseProgram(post_process.program);
+                gl::BindVertexArray(post_process.vao);
+                gl::BindTexture(gl::TEXTURE_2D, post_process.texture);
+
+                gl::Clear(gl::COLOR_BUFFER_BIT);
+                gl::DrawArrays(gl::TRIANGLE_STRIP, 0, 4);
+            }
+
+            self.check_for_gl_error();
+        }
+    }
+
+    // Draws each layer over the game in order, blended by its alpha and moved by its offset.
+    // Layers are clipped to the game's area so a moved layer can't spill into the letterbox
+    unsafe fn draw_layers(&mut self, layers: &[Layer]) {
+        while self.layer_textures.len() < layers.len() {
+            let mut texture = 0;
+            gl::GenTextures(1, &mut texture);
+            gl::BindTexture(gl::TEXTURE_2D, texture);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST as i32);
+            self.layer_textures.push(texture);
+        }
+
+        let bounding_box = self.bounding_box;
+        let (window_width, window_height) = (self.window_size.0 as f32, self.window_size.1 as f32);
+        let to_window = |ndc: f32, size: f32| ((ndc * 0.5 + 0.5) * size).round() as i32;
+        let scissor_x = to_window(bounding_box.min_x, window_width);
+        let scissor_y = to_window(bounding_box.min_y, window_height);
+        gl::Scissor(
+            scissor_x,
+            scissor_y,
+            to_window(bounding_box.max_x, window_width) - scissor_x,
+            to_window(bounding_box.max_y, window_height) - scissor_y,
+        );
+        gl::Enable(gl::SCISSOR_TEST);
+        gl::Enable(gl::BLEND);
+        // Alpha is added up separately so layers can't make an opaque game see-through.
+        // Premultiplied colors have already been scaled by their alpha
+        let src_color = if self.premultiplied_alpha {
+            gl::ONE
+        } else {
+            gl::SRC_ALPHA
+        };
+        gl::BlendFuncSeparate(
+            src_color,
+            gl::ONE_MINUS_SRC_ALPHA,
+            gl::ONE,
+            gl::ONE_MINUS_SRC_ALPHA,
+        );
+
+        // Game pixels in normalized device coordinates. The y axis points up in NDC
+        let pixel_size = (
+            (bounding_box.max_x - bounding_box.min_x) / self.width as f32,
+            (bounding_box.max_y - bounding_box.min_y) / self.height as f32,
+        );
+        for (layer, &texture) in layers.iter().zip(&self.layer_textures) {
+            debug_assert_eq!(layer.rgba.len(), (self.width * self.height) as usize * 4);
+
+            gl::BindTexture(gl::TEXTURE_2D, texture);
+            gl::TexImage2D(
+                gl::TEXTURE_2D,
+                0,
+                gl::RGBA as i32,
+                self.width as i32,
+                self.height as i32,
+                0,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                layer.rgba.as_ptr() as *const _,
+            );
+            gl::Uniform2f(
+                self.offset_location,
+                layer.offset.0 as f32 * pixel_size.0,
+                -layer.offset.1 as f32 * pixel_size.1,
+            );
+            gl::DrawArrays(gl::TRIANGLE_STRIP, 0, 4);
+        }
+
+        gl::Uniform2f(self.offset_location, 0.0, 0.0);
+        gl::Disable(gl::BLEND);
+        gl::Disable(gl::SCISSOR_TEST);
+        gl::BindTexture(gl::TEXTURE_2D, self.texture);
+    }
+
+    /// Reads back what was last drawn to the window as RGB bytes, 3 per pixel, with rows from
+    /// top to bottom. Has to be called before the buffers are swapped, since the back buffer's
+    /// contents are undefined afterwards.
+    pub fn read_framebuffer(&self) -> Vec<u8> {
+        let (width, height) = self.window_size;
+        let mut rgba = vec![0; width as usize * height as usize * 4];
+        unsafe {
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+            // RGBA is the only format OpenGL ES is guaranteed to read back
+            gl::ReadPixels(
+                0,
+                0,
+                width as i32,
+                height as i32,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                rgba.as_mut_ptr() as *mut _,
+            );
             self.check_for_gl_error();
         }
+        bottom_up_rgba_to_rgb(&rgba, width)
+    }
+
+    #[inline]
+    pub fn window_size(&self) -> (u32, u32) {
+        self.window_size
+    }
+
+    /// Sets the color adjustments applied when drawing. `(0.0, 1.0, 1.0)` leaves colors as they
+    /// are.
+    pub fn set_display_adjustments(&self, brightness: f32, contrast: f32, gamma: f32) {
+        unsafe {
+            gl::UseProgram(self.program);
+            gl::Uniform1f(self.brightness_location, brightness);
+            gl::Uniform1f(self.contrast_location, contrast);
+            gl::Uniform1f(self.gamma_location, gamma);
+        }
+    }
+
+    /// Whether the context has been lost, such as after a driver reset or a GPU switch. Nothing
+    /// drawn to a lost context shows up, so it has to be replaced along with everything in it.
+    pub fn is_context_lost(&self) -> bool {
+        unsafe {
+            gl::GetGraphicsResetStatus::is_loaded() && gl::GetGraphicsResetStatus() != gl::NO_ERROR
+        }
     }
 
     pub fn recalculate_dimensions_and_bounding_box(
@@ -213,6 +471,7 @@ impl Gl {
                     window_width,
                     window_height,
                     true,
+                    self.pixel_aspect_ratio,
                 )
             } else {
                 crate::platform::calculate_dimensions_and_bounding_box(
@@ -221,24 +480,37 @@ impl Gl {
                     window_width,
                     window_height,
                     false,
+                    self.pixel_aspect_ratio,
                 )
             };
 
+        self.window_size = (window_width, window_height);
         unsafe {
             gl::Viewport(0, 0, window_width as i32, window_height as i32);
 
+            if let Some(post_process) = &self.post_process {
+                post_process.resize(
+                    window_width,
+                    window_height,
+                    self.bounding_box,
+                    (self.width, self.height),
+                );
+                gl::UseProgram(self.program);
+                gl::BindTexture(gl::TEXTURE_2D, self.texture);
+            }
+
             gl::BindVertexArray(self.vao);
             gl::BindBuffer(gl::ARRAY_BUFFER, self.pos_vbo);
             let bounding_box = self.bounding_box;
             let pos_data_f32 = [
-                bounding_box.0,
-                bounding_box.1,
-                bounding_box.2,
-                bounding_box.1,
-                bounding_box.0,
-                bounding_box.3,
-                bounding_box.2,
-                bounding_box.3,
+                bounding_box.min_x,
+                bounding_box.min_y,
+                bounding_box.max_x,
+                bounding_box.min_y,
+                bounding_box.min_x,
+                bounding_box.max_y,
+                bounding_box.max_x,
+                bounding_box.max_y,
             ];
             let pos_data = bytemuck::must_cast_slice::<f32, u8>(&pos_data_f32);
             gl::BufferData(
@@ -249,7 +521,12 @@ impl Gl {
             );
         }
     }
-    pub fn current_bounding_box(&self) -> (f32, f32, f32, f32) {
+    #[inline]
+    pub fn renderer_info(&self) -> &RendererInfo {
+        &self.renderer_info
+    }
+
+    pub fn current_bounding_box(&self) -> BoundingBox {
         self.bounding_box
     }
 
@@ -264,6 +541,284 @@ impl Gl {
             let buffers = [self.pos_vbo, self.uv_vbo];
             gl::DeleteBuffers(2, buffers.as_ptr());
             gl::DeleteTextures(1, &self.texture);
+            gl::DeleteTextures(
+                self.layer_textures.len() as i32,
+                self.layer_textures.as_ptr(),
+            );
+
+            if let Some(border) = &self.border {
+                gl::DeleteVertexArrays(1, &border.vao);
+                gl::DeleteBuffers(1, &border.vbo);
+                gl::DeleteTextures(1, &border.texture);
+            }
+
+            if let Some(post_process) = &self.post_process {
+                gl::DeleteProgram(post_process.program);
+                gl::DeleteVertexArrays(1, &post_process.vao);
+                gl::DeleteBuffers(1, &post_process.vbo);
+                gl::DeleteFramebuffers(1, &post_process.framebuffer);
+                gl::DeleteTextures(1, &post_process.texture);
+            }
         }
     }
 }
+
+impl Border {
+    // Leaves the border's vertex array and texture bound
+    unsafe fn new(image: &Sprite) -> Self {
+        let mut vao = 0;
+        gl::GenVertexArrays(1, &mut vao);
+        gl::BindVertexArray(vao);
+
+        // Positions then UVs of a quad covering the whole window, flipped since the image's top
+        // row comes first
+        let mut vbo = 0;
+        gl::GenBuffers(1, &mut vbo);
+        gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
+        let quad_data = bytemuck::must_cast_slice::<f32, u8>(&[
+            -1.0, -1.0, 1.0, -1.0, -1.0, 1.0, 1.0, 1.0, 0.0, 1.0, 1.0, 1.0, 0.0, 0.0, 1.0, 0.0,
+        ]);
+        gl::BufferData(
+            gl::ARRAY_BUFFER,
+            quad_data.len() as isize,
+            quad_data.as_ptr() as *const _,
+            gl::STATIC_DRAW,
+        );
+        gl::EnableVertexAttribArray(0);
+        gl::VertexAttribPointer(0, 2, gl::FLOAT, 0, 0, std::ptr::null());
+        gl::EnableVertexAttribArray(1);
+        gl::VertexAttribPointer(1, 2, gl::FLOAT, 0, 0, (8 * 4) as *const _);
+
+        let mut texture = 0;
+        gl::GenTextures(1, &mut texture);
+        gl::BindTexture(gl::TEXTURE_2D, texture);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
+        // Only uploaded once, since the image never changes
+        gl::TexImage2D(
+            gl::TEXTURE_2D,
+            0,
+            gl::RGB as i32,
+            image.width() as i32,
+            image.height() as i32,
+            0,
+            gl::RGB,
+            gl::UNSIGNED_BYTE,
+            image.pixels().as_ptr() as *const _,
+        );
+
+        Self { vao, vbo, texture }
+    }
+}
+
+impl PostProcess {
+    unsafe fn new(version: GlVersion, fragment_source: &str) -> Self {
+        let program = create_program(version, include_str!("shader/vert.glsl"), fragment_source);
+        gl::UseProgram(program);
+        let resolution_location = gl::GetUniformLocation(program, c"resolution".as_ptr());
+        let game_rect_location = gl::GetUniformLocation(program, c"game_rect".as_ptr());
+        let game_size_location = gl::GetUniformLocation(program, c"game_size".as_ptr());
+
+        let mut vao = 0;
+        gl::GenVertexArrays(1, &mut vao);
+        gl::BindVertexArray(vao);
+
+        // Positions then UVs of a quad covering the whole window. The framebuffer's texture is
+        // bottom-up like the window, so the UVs aren't flipped
+        let mut vbo = 0;
+        gl::GenBuffers(1, &mut vbo);
+        gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
+        let quad_data = bytemuck::must_cast_slice::<f32, u8>(&[
+            -1.0, -1.0, 1.0, -1.0, -1.0, 1.0, 1.0, 1.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 1.0, 1.0,
+        ]);
+        gl::BufferData(
+            gl::ARRAY_BUFFER,
+            quad_data.len() as isize,
+            quad_data.as_ptr() as *const _,
+            gl::STATIC_DRAW,
+        );
+        gl::EnableVertexAttribArray(0);
+        gl::VertexAttribPointer(0, 2, gl::FLOAT, 0, 0, std::ptr::null());
+        gl::EnableVertexAttribArray(1);
+        gl::VertexAttribPointer(1, 2, gl::FLOAT, 0, 0, (8 * 4) as *const _);
+
+        let mut texture = 0;
+        gl::GenTextures(1, &mut texture);
+        gl::BindTexture(gl::TEXTURE_2D, texture);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
+
+        let mut framebuffer = 0;
+        gl::GenFramebuffers(1, &mut framebuffer);
+        gl::BindFramebuffer(gl::FRAMEBUFFER, framebuffer);
+        gl::FramebufferTexture2D(
+            gl::FRAMEBUFFER,
+            gl::COLOR_ATTACHMENT0,
+            gl::TEXTURE_2D,
+            texture,
+            0,
+        );
+        gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+
+        Self {
+            program,
+            vao,
+            vbo,
+            framebuffer,
+            texture,
+
+            resolution_location,
+            game_rect_location,
+            game_size_location,
+        }
+    }
+
+    // Matches the framebuffer to the window size and tells the shader where the game is drawn.
+    // Leaves the post-process program and the framebuffer's texture bound
+    unsafe fn resize(
+        &self,
+        window_width: u32,
+        window_height: u32,
+        bounding_box: BoundingBox,
+        game_size: (u32, u32),
+    ) {
+        gl::BindTexture(gl::TEXTURE_2D, self.texture);
+        gl::TexImage2D(
+            gl::TEXTURE_2D,
+            0,
+            gl::RGBA as i32,
+            window_width as i32,
+            window_height as i32,
+            0,
+            gl::RGBA,
+            gl::UNSIGNED_BYTE,
+            std::ptr::null(),
+        );
+
+        gl::UseProgram(self.program);
+        gl::Uniform2f(
+            self.resolution_location,
+            window_width as f32,
+            window_height as f32,
+        );
+        // The bounding box is in normalized device coordinates; convert it to texture
+        // coordinates
+        let to_uv = |ndc: f32| ndc * 0.5 + 0.5;
+        gl::Uniform4f(
+            self.game_rect_location,
+            to_uv(bounding_box.min_x),
+            to_uv(bounding_box.min_y),
+            to_uv(bounding_box.max_x),
+            to_uv(bounding_box.max_y),
+        );
+        gl::Uniform2f(
+            self.game_size_location,
+            game_size.0 as f32,
+            game_size.1 as f32,
+        );
+    }
+}
+
+// Flips rows read back from OpenGL, which come bottom row first, and drops the alpha channel
+fn bottom_up_rgba_to_rgb(rgba: &[u8], width: u32) -> Vec<u8> {
+    rgba.chunks_exact(width as usize * 4)
+        .rev()
+        .flat_map(|row| row.chunks_exact(4).flat_map(|pixel| &pixel[..3]))
+        .copied()
+        .collect()
+}
+
+// Reads one of the strings describing the context, which is empty if the driver doesn't give it
+unsafe fn gl_string(name: u32) -> String {
+    let string = gl::GetString(name);
+    if string.is_null() {
+        return String::new();
+    }
+    std::ffi::CStr::from_ptr(string as *const _)
+        .to_string_lossy()
+        .into_owned()
+}
+
+unsafe fn create_program(version: GlVersion, vertex_source: &str, fragment_source: &str) -> u32 {
+    let program = gl::CreateProgram();
+
+    unsafe fn compile_shader(
+        program: u32,
+        version: GlVersion,
+        source: &str,
+        shader_type: u32,
+    ) -> u32 {
+        let source = version.translate_shader(source, shader_type);
+        let shader = gl::CreateShader(shader_type);
+        gl::ShaderSource(
+            shader,
+            1,
+            &(source.as_bytes().as_ptr() as *const _),
+            &(source.len() as i32),
+        );
+        gl::CompileShader(shader);
+
+        #[cfg(debug_assertions)]
+        {
+            let mut status = 0;
+            gl::GetShaderiv(shader, gl::COMPILE_STATUS, &mut status);
+
+            if status != 1 {
+                panic!("shader compilation error");
+            }
+        }
+
+        gl::AttachShader(program, shader);
+
+        shader
+    }
+
+    let vertex_shader = compile_shader(program, version, vertex_source, gl::VERTEX_SHADER);
+    let fragment_shader = compile_shader(program, version, fragment_source, gl::FRAGMENT_SHADER);
+
+    // GLSL 1.20 can't give attributes locations itself
+    gl::BindAttribLocation(program, 0, c"position".as_ptr());
+    gl::BindAttribLocation(program, 1, c"inUV".as_ptr());
+    gl::LinkProgram(program);
+
+    #[cfg(debug_assertions)]
+    {
+        let mut status = 0;
+        gl::GetProgramiv(program, gl::LINK_STATUS, &mut status);
+        if status != 1 {
+            panic!("program error");
+        }
+    }
+
+    unsafe fn delete_shader(program: u32, shader: u32) {
+        gl::DetachShader(program, shader);
+        gl::DeleteShader(shader);
+    }
+
+    delete_shader(program, vertex_shader);
+    delete_shader(program, fragment_shader);
+
+    program
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_back_rows_are_flipped_and_lose_their_alpha() {
+        #[rustfmt::skip]
+        let rgba = [
+            1, 2, 3, 255,   4, 5, 6, 0,
+            7, 8, 9, 128,   10, 11, 12, 255,
+        ];
+        assert_eq!(
+            bottom_up_rgba_to_rgb(&rgba, 2),
+            [7, 8, 9, 10, 11, 12, 1, 2, 3, 4, 5, 6]
+        );
+    }
+}
@@ -0,0 +1,34 @@
+#version 330
+
+uniform sampler2D tex;
+// The game's area of the window as (left, bottom, right, top) texture coordinates
+uniform vec4 game_rect;
+uniform vec2 game_size;
+
+in mediump vec2 uv;
+out lowp vec4 frag_color;
+
+const float CURVATURE = 0.08;
+
+void main() {
+    // Position within the game area, from -1 to 1
+    vec2 pos = (uv - game_rect.xy) / (game_rect.zw - game_rect.xy) * 2.0 - 1.0;
+
+    // Barrel distortion: bulge the middle outwards like a curved screen
+    pos *= 1.0 + CURVATURE * dot(pos, pos);
+    if (abs(pos.x) > 1.0 || abs(pos.y) > 1.0) {
+        frag_color = vec4(0.0);
+        return;
+    }
+    vec2 curved_uv = game_rect.xy + (pos * 0.5 + 0.5) * (game_rect.zw - game_rect.xy);
+    vec4 texel = texture(tex, curved_uv);
+    vec3 color = texel.rgb;
+
+    // Faint scanlines, then darken towards the edges
+    float row = (1.0 - (pos.y * 0.5 + 0.5)) * game_size.y;
+    color *= 0.85 + 0.15 * cos(fract(row) * 6.2831853);
+    vec2 edge = 1.0 - pos * pos;
+    color *= pow(edge.x * edge.y, 0.25);
+
+    frag_color = vec4(color, texel.a);
+}
@@ -1,10 +1,25 @@
 #version 330
 
 uniform sampler2D tex;
+uniform float brightness;
+uniform float contrast;
+uniform float gamma;
+// Pixels of this color are drawn fully transparent if `use_color_key` is set
+uniform vec3 color_key;
+uniform bool use_color_key;
 
 in mediump vec2 uv;
 out lowp vec4 frag_color;
 
 void main() {
-    frag_color = texture(tex, uv);
+    // The game's own texture has no alpha channel, so its alpha is always 1
+    vec4 texel = texture(tex, uv);
+    if (use_color_key && all(lessThan(abs(texel.rgb - color_key), vec3(0.5 / 255.0)))) {
+        frag_color = vec4(0.0);
+        return;
+    }
+
+    vec3 color = pow(texel.rgb, vec3(1.0 / gamma));
+    color = (color - 0.5) * contrast + 0.5 + brightness;
+    frag_color = vec4(clamp(color, 0.0, 1.0), texel.a);
 }
@@ -0,0 +1,22 @@
+#version 330
+
+uniform sampler2D tex;
+// The game's area of the window as (left, bottom, right, top) texture coordinates
+uniform vec4 game_rect;
+uniform vec2 game_size;
+
+in mediump vec2 uv;
+out lowp vec4 frag_color;
+
+void main() {
+    vec4 texel = texture(tex, uv);
+    vec3 color = texel.rgb;
+
+    // Darken the bottom half of every game pixel row
+    float row = (game_rect.w - uv.y) / (game_rect.w - game_rect.y) * game_size.y;
+    if (fract(row) > 0.5) {
+        color *= 0.6;
+    }
+
+    frag_color = vec4(color, texel.a);
+}
@@ -5,7 +5,10 @@ layout(location = 1) in vec2 inUV;
 
 out mediump vec2 uv;
 
+// Moves the quad, for drawing layers scrolled by some amount
+uniform vec2 offset;
+
 void main() {
-    gl_Position = vec4(position, 0.0, 1.0);
+    gl_Position = vec4(position + offset, 0.0, 1.0);
     uv = inUV;
 }
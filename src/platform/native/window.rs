@@ -1,17 +1,37 @@
-use std::{thread, time};
+use std::collections::HashMap;
 
 use glfw::{Context, PixelImage};
 
-use crate::platform::{self, WindowClient, WindowEvent};
+use crate::{
+    platform::{
+        self, BoundingBox, DisplaySettings, FrameTiming, Layer, WindowClient, WindowCommand,
+        WindowEvent,
+    },
+    CursorIcon, SwapInterval,
+};
 
-use super::Gl;
+use super::{Gl, GlVersion};
 
 pub struct GLFWWindow {
+    // Declared first so the cursors are destroyed before GLFW might be terminated
+    cursors: HashMap<CursorIcon, glfw::Cursor>,
+    cursor_icon: CursorIcon,
+
     glfw: glfw::Glfw,
     window: glfw::PWindow,
     events: glfw::GlfwReceiver<(f64, glfw::WindowEvent)>,
 
     fullscreen_target_dimensions: Option<(u32, u32)>,
+    monitor_refresh_rate: Option<u32>,
+    scancode_keys: HashMap<glfw::Scancode, crate::Key>,
+
+    // Kept to recreate the window if the GL context is lost
+    title: String,
+    icon: Option<crate::Icon>,
+    display: DisplaySettings,
+    display_adjustments: (f32, f32, f32),
+    swap_interval: SwapInterval,
+    gl_version: GlVersion,
 
     gl: super::Gl,
 }
@@ -23,62 +43,76 @@ impl crate::platform::WindowTrait for GLFWWindow {
         title: &str,
         icon: Option<crate::Icon>,
         fullscreen: bool,
+        borderless: bool,
+        display: DisplaySettings,
     ) -> Self {
-        let mut glfw = glfw::init(|error, description| {
-            glfw::fail_on_errors(error, description);
+        let mut glfw = glfw::init(|error, description| match error {
+            // Expected while trying OpenGL versions the system doesn't have
+            glfw::Error::ApiUnavailable | glfw::Error::VersionUnavailable => {}
+            _ => glfw::fail_on_errors(error, description),
         })
         .expect("failed to create GLFW instance");
 
-        glfw.window_hint(glfw::WindowHint::ContextVersionMajor(3));
-        glfw.window_hint(glfw::WindowHint::ContextVersionMinor(3));
-        glfw.window_hint(glfw::WindowHint::OpenGlForwardCompat(true));
-        glfw.window_hint(glfw::WindowHint::OpenGlProfile(
-            glfw::OpenGlProfileHint::Core,
+        glfw.window_hint(glfw::WindowHint::ContextRobustness(
+            glfw::ContextRobustnessHint::LoseContextOnReset,
         ));
 
-        let (mut window, events) = glfw.with_primary_monitor(|glfw, monitor| {
+        if borderless {
+            glfw.window_hint(glfw::WindowHint::Decorated(false));
+        }
+        if display.transparent_color_key.is_some() {
+            glfw.window_hint(glfw::WindowHint::TransparentFramebuffer(true));
+        }
+
+        let (mut window, events, gl_version) = glfw.with_primary_monitor(|glfw, monitor| {
             let monitor = monitor.expect("failed to get the primary monitor");
             let monitor_size = monitor
                 .get_video_mode()
                 .map_or((480, 360), |mode| (mode.width, mode.height));
 
-            let window_size =
-                crate::get_window_size(width, height, monitor_size.0 as u32, monitor_size.1 as u32);
-
-            glfw.create_window(
-                window_size.0 as u32,
-                window_size.1 as u32,
-                title,
-                glfw::WindowMode::Windowed,
-            )
-            .expect("failed to create GLFW window")
-        });
-
-        if let Some(icon) = icon {
-            let pixels_u8 = icon.rgba;
-            let mut pixels = Vec::with_capacity(pixels_u8.len() / 4);
-            for [r, g, b, a] in pixels_u8
-                .chunks_exact(4)
-                .map(|x| TryInto::<[u8; 4]>::try_into(x).unwrap())
-            {
-                pixels.push(u32::from_be_bytes([r, g, b, a]));
+            // A borderless window covers the whole monitor at its current resolution
+            let window_size = if borderless {
+                (monitor_size.0 as f32, monitor_size.1 as f32)
+            } else {
+                crate::get_window_size(
+                    width,
+                    height,
+                    monitor_size.0,
+                    monitor_size.1,
+                    display.pixel_aspect_ratio,
+                )
+            };
+
+            let (mut window, events, gl_version) = GlVersion::FALLBACKS
+                .into_iter()
+                .find_map(|version| {
+                    set_context_hints(glfw, version);
+                    let (window, events) = create_window(
+                        glfw,
+                        window_size.0 as u32,
+                        window_size.1 as u32,
+                        title,
+                        icon.as_ref(),
+                    )?;
+                    Some((window, events, version))
+                })
+                .expect("failed to create a window with any supported OpenGL version");
+            if borderless {
+                let (x, y) = monitor.get_pos();
+                window.set_pos(x, y);
             }
-            window.set_icon_from_pixels(vec![PixelImage {
-                width: icon.width,
-                height: icon.height,
-                pixels,
-            }]);
-        }
-
-        window.set_size_polling(true);
-        window.set_close_polling(true);
-        window.set_key_polling(true);
-        window.set_focus_polling(true);
-        window.set_mouse_button_polling(true);
-        window.set_cursor_pos_polling(true);
-        window.set_cursor_enter_polling(true);
+            (window, events, gl_version)
+        });
+        let monitor_refresh_rate = glfw.with_primary_monitor(|_, monitor| {
+            monitor
+                .and_then(|monitor| monitor.get_video_mode())
+                .map(|mode| mode.refresh_rate)
+                .filter(|&rate| rate != 0)
+        });
 
-        let mut gl = Gl::new(width, height, |s| window.get_proc_address(s) as _);
+        let mut gl = Gl::new(gl_version, width, height, &display, |s| {
+            window.get_proc_address(s) as _
+        });
 
         let window_size = window.get_size();
 
@@ -90,14 +124,26 @@ impl crate::platform::WindowTrait for GLFWWindow {
             fullscreen_target_dimensions,
         );
 
-        glfw.set_swap_interval(glfw::SwapInterval::Sync(1));
+        glfw.set_swap_interval(glfw_swap_interval(&glfw, SwapInterval::On));
 
         Self {
+            cursors: HashMap::new(),
+            cursor_icon: CursorIcon::Default,
+
             glfw,
             window,
             events,
 
             fullscreen_target_dimensions,
+            monitor_refresh_rate,
+            scancode_keys: super::scancode_keys(),
+
+            title: title.to_owned(),
+            icon,
+            display,
+            display_adjustments: (0.0, 1.0, 1.0),
+            swap_interval: SwapInterval::On,
+            gl_version,
 
             gl,
         }
@@ -109,99 +155,348 @@ impl crate::platform::WindowTrait for GLFWWindow {
         (window_size.0 as u32, window_size.1 as u32)
     }
 
-    fn run<T>(&mut self, client: &mut T)
-    where
-        T: WindowClient,
-    {
-        let frame_nanos = 1_000_000_000 / 60;
+    fn monitor_refresh_rate(&self) -> Option<u32> {
+        self.monitor_refresh_rate
+    }
 
-        let instant = time::Instant::now();
-        let mut next_frame_time = instant.elapsed().as_millis() + frame_nanos;
+    fn renderer_info(&self) -> crate::RendererInfo {
+        self.gl.renderer_info().clone()
+    }
 
-        loop {
-            self.glfw.poll_events();
+    fn window_position(&self) -> (i32, i32) {
+        self.window.get_pos()
+    }
+
+    fn set_window_position(&mut self, x: i32, y: i32) {
+        self.window.set_pos(x, y);
+    }
 
-            for (_, glfw_event) in glfw::flush_messages(&self.events) {
-                use crate::platform::WindowEvent as W;
-                use glfw::WindowEvent as E;
-                let event = match glfw_event {
-                    E::Key(key, _, action, _) => W::Key {
+    fn step<T>(
+        &mut self,
+        timing: &mut FrameTiming,
+        clock: &dyn crate::clock::Clock,
+        client: &mut T,
+    ) -> Option<u64>
+    where
+        T: WindowClient,
+    {
+        self.glfw.poll_events();
+
+        for (_, glfw_event) in glfw::flush_messages(&self.events) {
+            use crate::platform::WindowEvent as W;
+            use glfw::WindowEvent as E;
+            let event = match glfw_event {
+                E::Key(key, scancode, action, _) => {
+                    if let Some(&physical_key) = self.scancode_keys.get(&scancode) {
+                        if action != glfw::Action::Repeat {
+                            client.handle_event(W::PhysicalKey {
+                                key: physical_key,
+                                pressed: action == glfw::Action::Press,
+                            });
+                        }
+                    }
+                    W::Key {
                         key: match super::glfw_key_to_key(key) {
                             Some(key) => key,
                             None => continue,
                         },
-                        pressed: match action {
-                            glfw::Action::Release => false,
-                            glfw::Action::Press => true,
-                            glfw::Action::Repeat => continue,
-                        },
-                    },
-                    E::Size(window_width, window_height) => {
-                        self.gl.recalculate_dimensions_and_bounding_box(
-                            window_width as u32,
-                            window_height as u32,
-                            self.fullscreen_target_dimensions,
-                        );
-                        let (width, height) = self.gl.dimensions();
-                        W::WindowResize {
-                            width,
-                            height,
-                            window_width: window_width as u32,
-                            window_height: window_height as u32,
-                            new_bounding_box: self.gl.current_bounding_box(),
-                        }
+                        pressed: action != glfw::Action::Release,
+                        repeat: action == glfw::Action::Repeat,
                     }
-                    E::Close => W::WindowClose,
-                    E::Focus(focused) => WindowEvent::FocusChanged { focused },
-                    E::MouseButton(mouse_button, action, ..) => W::MouseButton {
-                        button: match mouse_button {
-                            glfw::MouseButtonLeft => platform::MouseButton::Left,
-                            glfw::MouseButtonMiddle => platform::MouseButton::Middle,
-                            glfw::MouseButtonRight => platform::MouseButton::Right,
-                            _ => continue,
-                        },
-                        pressed: match action {
-                            glfw::Action::Release => false,
-                            glfw::Action::Press => true,
-                            glfw::Action::Repeat => continue,
-                        },
+                }
+                E::Size(window_width, window_height) => {
+                    self.gl.recalculate_dimensions_and_bounding_box(
+                        window_width as u32,
+                        window_height as u32,
+                        self.fullscreen_target_dimensions,
+                    );
+                    let (width, height) = self.gl.dimensions();
+                    W::WindowResize {
+                        width,
+                        height,
+                        window_width: window_width as u32,
+                        window_height: window_height as u32,
+                        new_bounding_box: self.gl.current_bounding_box(),
+                    }
+                }
+                // Part of the window was uncovered, or it was restored or resized, and its contents
+                // may be gone. Drawn right away so it doesn't show garbage until the next frame
+                E::Refresh => {
+                    self.gl.draw(client.get_pixels(), client.get_layers());
+                    self.window.swap_buffers();
+                    continue;
+                }
+                E::Close => W::WindowClose,
+                E::Pos(x, y) => W::WindowMove { x, y },
+                E::Focus(focused) => WindowEvent::FocusChanged { focused },
+                E::MouseButton(mouse_button, action, ..) => W::MouseButton {
+                    button: match mouse_button {
+                        glfw::MouseButtonLeft => platform::MouseButton::Left,
+                        glfw::MouseButtonMiddle => platform::MouseButton::Middle,
+                        glfw::MouseButtonRight => platform::MouseButton::Right,
+                        _ => continue,
                     },
-                    E::CursorPos(x, y) => W::MousePos {
-                        x: x as u32,
-                        y: y as u32,
+                    pressed: match action {
+                        glfw::Action::Release => false,
+                        glfw::Action::Press => true,
+                        glfw::Action::Repeat => continue,
                     },
-                    E::CursorEnter(entered) => W::MouseEnter { entered },
-                    E::Scroll(_, _) => todo!(),
-                    _ => continue,
-                };
-
-                client.handle_event(event);
-            }
+                },
+                E::CursorPos(x, y) => W::MousePos {
+                    x: x.floor() as i32,
+                    y: y.floor() as i32,
+                },
+                E::CursorEnter(entered) => W::MouseEnter { entered },
+                E::Scroll(x, y) => W::Scroll {
+                    x: x as f32,
+                    y: y as f32,
+                },
+                E::Char(c) => W::Char(c),
+                _ => continue,
+            };
+
+            client.handle_event(event);
+        }
 
-            let cur_time = instant.elapsed().as_nanos();
+        let cur_time = clock.now_nanos();
 
-            while cur_time >= next_frame_time {
-                next_frame_time += frame_nanos;
+        while timing.take_due_frame(cur_time) {
+            if !client.frame(cur_time) {
+                return None;
+            }
 
-                if !client.frame(cur_time as u64) {
-                    return;
+            for command in client.take_commands() {
+                match command {
+                    WindowCommand::Position { x, y } => self.set_window_position(x, y),
+                    WindowCommand::CursorPosition { x, y } => {
+                        let (x, y) = self.gl.current_bounding_box().game_to_window(
+                            x,
+                            y,
+                            self.gl.window_size(),
+                            self.gl.dimensions(),
+                        );
+                        self.window.set_cursor_pos(x as f64, y as f64);
+                    }
+                    WindowCommand::CursorIcon(icon) => self.set_cursor_icon(icon),
+                    WindowCommand::Opacity(opacity) => self.window.set_opacity(opacity),
+                    WindowCommand::SwapInterval(interval) => {
+                        self.swap_interval = interval;
+                        self.glfw
+                            .set_swap_interval(glfw_swap_interval(&self.glfw, interval));
+                    }
+                    WindowCommand::CustomCursor {
+                        width,
+                        height,
+                        rgba,
+                        hot_x,
+                        hot_y,
+                    } => self.set_custom_cursor(width, height, &rgba, hot_x, hot_y),
+                    WindowCommand::DisplayAdjustments {
+                        brightness,
+                        contrast,
+                        gamma,
+                    } => {
+                        self.display_adjustments = (brightness, contrast, gamma);
+                        self.gl.set_display_adjustments(brightness, contrast, gamma);
+                    }
+                    WindowCommand::RequestAttention => self.window.request_attention(),
                 }
             }
+        }
 
-            if cur_time < next_frame_time {
-                self.gl.draw(client.get_pixels());
-                self.window.swap_buffers();
+        self.gl.draw(client.get_pixels(), client.get_layers());
+        self.window.swap_buffers();
 
-                thread::sleep(time::Duration::from_nanos(
-                    (next_frame_time - cur_time) as u64,
-                ));
-            }
+        if self.gl.is_context_lost() {
+            self.recreate_window();
         }
+
+        Some(timing.nanos_until_next_frame(cur_time))
     }
 
-    fn current_bounding_box(&self) -> (f32, f32, f32, f32) {
+    fn current_bounding_box(&self) -> BoundingBox {
         self.gl.current_bounding_box()
     }
+
+    fn read_framebuffer(&mut self, pixels: &[u8], layers: &[Layer]) -> (u32, u32, Vec<u8>) {
+        // Drawn again since the last frame's back buffer is gone after it was swapped
+        self.gl.draw(pixels, layers);
+        let (width, height) = self.gl.window_size();
+        (width, height, self.gl.read_framebuffer())
+    }
+}
+
+impl GLFWWindow {
+    // Replaces the window and its GL context after the context is lost. GLFW ties every context
+    // to a window, so a new context means a new window; the game itself is left untouched and
+    // its next frame is drawn to the new one
+    fn recreate_window(&mut self) {
+        eprintln!("OpenGL context lost, recreating the window");
+
+        let (window_width, window_height) = self.window.get_size();
+        let (x, y) = self.window.get_pos();
+        let opacity = self.window.get_opacity();
+        let (mut window, events) = create_window(
+            &mut self.glfw,
+            window_width as u32,
+            window_height as u32,
+            &self.title,
+            self.icon.as_ref(),
+        )
+        // The context hints are still the ones that worked for the first window
+        .expect("failed to create GLFW window");
+        window.set_pos(x, y);
+        window.set_opacity(opacity);
+
+        // The current cursor would be destroyed along with the old window
+        let mut old_window = std::mem::replace(&mut self.window, window);
+        if let Some(cursor) = old_window.set_cursor(None) {
+            self.cursors.insert(self.cursor_icon, cursor);
+        }
+        // Everything in the old context is already gone, so there's nothing to delete
+        drop(old_window);
+        self.events = events;
+
+        self.window.make_current();
+        let (width, height) = self.gl.dimensions();
+        let window = &mut self.window;
+        self.gl = Gl::new(self.gl_version, width, height, &self.display, |s| {
+            window.get_proc_address(s) as _
+        });
+        self.gl.recalculate_dimensions_and_bounding_box(
+            window_width as u32,
+            window_height as u32,
+            self.fullscreen_target_dimensions,
+        );
+        let (brightness, contrast, gamma) = self.display_adjustments;
+        self.gl.set_display_adjustments(brightness, contrast, gamma);
+        self.glfw
+            .set_swap_interval(glfw_swap_interval(&self.glfw, self.swap_interval));
+
+        self.set_cursor_icon(self.cursor_icon);
+    }
+
+    fn set_cursor_icon(&mut self, icon: CursorIcon) {
+        let standard_cursor = match icon {
+            CursorIcon::Default => None,
+            CursorIcon::Hand => Some(glfw::StandardCursor::Hand),
+            CursorIcon::IBeam => Some(glfw::StandardCursor::IBeam),
+            CursorIcon::Crosshair => Some(glfw::StandardCursor::Crosshair),
+            CursorIcon::HorizontalResize => Some(glfw::StandardCursor::HResize),
+            CursorIcon::VerticalResize => Some(glfw::StandardCursor::VResize),
+            CursorIcon::Custom => None,
+        };
+        // Created cursors are kept around for reuse rather than destroyed when swapped out
+        let cursor = match standard_cursor {
+            Some(standard_cursor) => Some(
+                self.cursors
+                    .remove(&icon)
+                    .unwrap_or_else(|| glfw::Cursor::standard(standard_cursor)),
+            ),
+            // Shows the default cursor if no custom cursor has been set
+            None if icon == CursorIcon::Custom => self.cursors.remove(&icon),
+            None => None,
+        };
+        if let Some(previous) = self.window.set_cursor(cursor) {
+            self.cursors.insert(self.cursor_icon, previous);
+        }
+        self.cursor_icon = icon;
+    }
+
+    fn set_custom_cursor(&mut self, width: u32, height: u32, rgba: &[u8], hot_x: u32, hot_y: u32) {
+        let cursor = glfw::Cursor::create_from_pixels(
+            rgba_to_pixel_image(width, height, rgba),
+            hot_x,
+            hot_y,
+        );
+        if self.cursor_icon == CursorIcon::Custom {
+            // The old custom cursor is returned and dropped, freeing it
+            self.window.set_cursor(Some(cursor));
+        } else {
+            self.cursors.insert(CursorIcon::Custom, cursor);
+        }
+    }
+}
+
+// Adaptive vsync needs a driver extension; without it, falls back to regular vsync
+fn glfw_swap_interval(glfw: &glfw::Glfw, interval: SwapInterval) -> glfw::SwapInterval {
+    match interval {
+        SwapInterval::Off => glfw::SwapInterval::None,
+        SwapInterval::On => glfw::SwapInterval::Sync(1),
+        SwapInterval::Adaptive
+            if glfw.extension_supported("WGL_EXT_swap_control_tear")
+                || glfw.extension_supported("GLX_EXT_swap_control_tear") =>
+        {
+            glfw::SwapInterval::Adaptive
+        }
+        SwapInterval::Adaptive => glfw::SwapInterval::Sync(1),
+    }
+}
+
+// Asks for a context of `version` in the windows created after this
+fn set_context_hints(glfw: &mut glfw::Glfw, version: GlVersion) {
+    let (api, major, minor) = match version {
+        GlVersion::Gl33 => (glfw::ClientApiHint::OpenGl, 3, 3),
+        GlVersion::Gles30 => (glfw::ClientApiHint::OpenGlEs, 3, 0),
+        GlVersion::Gl21 => (glfw::ClientApiHint::OpenGl, 2, 1),
+    };
+    glfw.window_hint(glfw::WindowHint::ClientApi(api));
+    glfw.window_hint(glfw::WindowHint::ContextVersionMajor(major));
+    glfw.window_hint(glfw::WindowHint::ContextVersionMinor(minor));
+    // Profiles only exist from OpenGL 3.2 on
+    let is_core = version == GlVersion::Gl33;
+    glfw.window_hint(glfw::WindowHint::OpenGlForwardCompat(is_core));
+    glfw.window_hint(glfw::WindowHint::OpenGlProfile(if is_core {
+        glfw::OpenGlProfileHint::Core
+    } else {
+        glfw::OpenGlProfileHint::Any
+    }));
+}
+
+fn create_window(
+    glfw: &mut glfw::Glfw,
+    width: u32,
+    height: u32,
+    title: &str,
+    icon: Option<&crate::Icon>,
+) -> Option<(glfw::PWindow, glfw::GlfwReceiver<(f64, glfw::WindowEvent)>)> {
+    let (mut window, events) =
+        glfw.create_window(width, height, title, glfw::WindowMode::Windowed)?;
+
+    if let Some(icon) = icon {
+        window.set_icon_from_pixels(vec![rgba_to_pixel_image(
+            icon.width,
+            icon.height,
+            &icon.rgba,
+        )]);
+    }
+
+    window.set_size_polling(true);
+    window.set_pos_polling(true);
+    window.set_close_polling(true);
+    window.set_key_polling(true);
+    window.set_focus_polling(true);
+    window.set_mouse_button_polling(true);
+    window.set_cursor_pos_polling(true);
+    window.set_cursor_enter_polling(true);
+    window.set_scroll_polling(true);
+    window.set_char_polling(true);
+    window.set_refresh_polling(true);
+
+    Some((window, events))
+}
+
+fn rgba_to_pixel_image(width: u32, height: u32, rgba: &[u8]) -> PixelImage {
+    // GLFW reads the pixels as bytes in RGBA order, so pack them in native byte order
+    let pixels = rgba
+        .chunks_exact(4)
+        .map(|x| u32::from_ne_bytes(x.try_into().unwrap()))
+        .collect();
+    PixelImage {
+        width,
+        height,
+        pixels,
+    }
 }
 
 impl Drop for GLFWWindow {
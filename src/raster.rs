@@ -0,0 +1,483 @@
+//! Drawing onto plain pixel buffers.
+//!
+//! Nothing here needs the operating system, only `core` and `alloc`, so this module can be
+//! reused in `no_std` code such as embedded or WASM targets. [`draw`](crate::draw) re-exports
+//! all of it alongside the helpers that do need `std`. The `no-std-check` crate builds this
+//! module with `#![no_std]` to keep it that way.
+
+use alloc::{vec, vec::Vec};
+
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Copy, Default)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Rect {
+    #[inline]
+    pub fn new(x: i32, y: i32, width: u32, height: u32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    #[inline]
+    pub fn right(&self) -> i32 {
+        self.x + self.width as i32
+    }
+    #[inline]
+    pub fn bottom(&self) -> i32 {
+        self.y + self.height as i32
+    }
+
+    #[inline]
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        x >= self.x && x < self.right() && y >= self.y && y < self.bottom()
+    }
+
+    /// The area covered by both rectangles. Empty if they don't overlap.
+    pub fn intersection(&self, other: Rect) -> Rect {
+        let (x, y) = (self.x.max(other.x), self.y.max(other.y));
+        let (right, bottom) = (
+            self.right().min(other.right()),
+            self.bottom().min(other.bottom()),
+        );
+        Rect::new(x, y, (right - x).max(0) as u32, (bottom - y).max(0) as u32)
+    }
+}
+
+/// Views a buffer of RGB bytes, 3 per pixel, as pixels, without copying.
+///
+/// # Panics
+///
+/// Panics if the buffer's length isn't a multiple of 3.
+#[inline]
+pub fn rgb_pixels(bytes: &[u8]) -> &[[u8; 3]] {
+    assert!(
+        bytes.len().is_multiple_of(3),
+        "RGB buffer length must be a multiple of 3"
+    );
+    bytemuck::cast_slice(bytes)
+}
+
+/// Mutable version of [`rgb_pixels`].
+///
+/// # Panics
+///
+/// Panics if the buffer's length isn't a multiple of 3.
+#[inline]
+pub fn rgb_pixels_mut(bytes: &mut [u8]) -> &mut [[u8; 3]] {
+    assert!(
+        bytes.len().is_multiple_of(3),
+        "RGB buffer length must be a multiple of 3"
+    );
+    bytemuck::cast_slice_mut(bytes)
+}
+
+/// Sets the pixel at `(x, y)`, doing nothing if it's outside the buffer.
+#[inline]
+pub fn set_pixel(pixels: &mut [[u8; 3]], width: u32, height: u32, x: i32, y: i32, color: [u8; 3]) {
+    if x >= 0 && x < width as i32 && y >= 0 && y < height as i32 {
+        pixels[(x + y * width as i32) as usize] = color;
+    }
+}
+
+/// Blends `color` over `pixel` with the given opacity, where 255 is fully opaque.
+#[inline]
+pub fn blend_pixel(pixel: &mut [u8; 3], color: [u8; 3], alpha: u8) {
+    for (channel, src) in pixel.iter_mut().zip(color) {
+        let blended = src as u32 * alpha as u32 + *channel as u32 * (255 - alpha as u32);
+        *channel = ((blended + 127) / 255) as u8;
+    }
+}
+
+/// Blends a color with premultiplied alpha over `pixel`: `color` has already been multiplied
+/// by `alpha`, so only the pixel underneath is scaled. Premultiplied colors can be filtered and
+/// scaled without the dark fringes that straight alpha gets at transparent edges.
+#[inline]
+pub fn blend_pixel_premultiplied(pixel: &mut [u8; 3], color: [u8; 3], alpha: u8) {
+    for (channel, src) in pixel.iter_mut().zip(color) {
+        let underneath = (*channel as u32 * (255 - alpha as u32) + 127) / 255;
+        *channel = (src as u32 + underneath).min(255) as u8;
+    }
+}
+
+/// Fills `rect`, clipped to the buffer.
+pub fn fill_rect(pixels: &mut [[u8; 3]], width: u32, height: u32, rect: Rect, color: [u8; 3]) {
+    let rect = rect.intersection(Rect::new(0, 0, width, height));
+    for y in rect.y..rect.bottom() {
+        let row = (y as u32 * width) as usize;
+        pixels[row + rect.x as usize..row + rect.right() as usize].fill(color);
+    }
+}
+
+/// A mutable view into a rectangular region of a pixel buffer.
+///
+/// Coordinates are relative to the region's top-left corner, and anything drawn outside of the
+/// region is clipped, so code given a `SubImage` can't draw over the rest of the buffer.
+pub struct SubImage<'a> {
+    pixels: &'a mut [[u8; 3]],
+    buffer_width: u32,
+    // Offset of the view from the buffer's top-left corner
+    x: i32,
+    y: i32,
+    // The part of the view that's inside the buffer, relative to the buffer
+    clip: Rect,
+    width: u32,
+    height: u32,
+}
+
+impl<'a> SubImage<'a> {
+    /// Creates a view of `rect` within a buffer `buffer_width` pixels wide.
+    ///
+    /// `rect` may extend past the buffer; those parts are clipped.
+    pub fn new(pixels: &'a mut [[u8; 3]], buffer_width: u32, rect: Rect) -> Self {
+        let buffer_height = pixels.len() as u32 / buffer_width;
+        Self {
+            pixels,
+            buffer_width,
+            x: rect.x,
+            y: rect.y,
+            clip: rect.intersection(Rect::new(0, 0, buffer_width, buffer_height)),
+            width: rect.width,
+            height: rect.height,
+        }
+    }
+
+    #[inline]
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+    #[inline]
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Sets the pixel at `(x, y)` relative to the view, doing nothing if it's outside.
+    #[inline]
+    pub fn set_pixel(&mut self, x: i32, y: i32, color: [u8; 3]) {
+        let (x, y) = (self.x + x, self.y + y);
+        if self.clip.contains(x, y) {
+            self.pixels[(x + y * self.buffer_width as i32) as usize] = color;
+        }
+    }
+
+    /// Fills `rect` relative to the view, clipped to the view.
+    pub fn fill_rect(&mut self, rect: Rect, color: [u8; 3]) {
+        let rect = Rect::new(self.x + rect.x, self.y + rect.y, rect.width, rect.height)
+            .intersection(self.clip);
+        for y in rect.y..rect.bottom() {
+            let row = (y as u32 * self.buffer_width) as usize;
+            self.pixels[row + rect.x as usize..row + rect.right() as usize].fill(color);
+        }
+    }
+
+    // Further restricts drawing to `clip`, relative to the buffer
+    #[inline]
+    pub(crate) fn clipped_to(mut self, clip: Rect) -> Self {
+        self.clip = self.clip.intersection(clip);
+        self
+    }
+
+    /// Creates a view of `rect` within this view, clipped to this view.
+    pub fn sub_image(&mut self, rect: Rect) -> SubImage<'_> {
+        let (x, y) = (self.x + rect.x, self.y + rect.y);
+        SubImage {
+            pixels: self.pixels,
+            buffer_width: self.buffer_width,
+            x,
+            y,
+            clip: Rect::new(x, y, rect.width, rect.height).intersection(self.clip),
+            width: rect.width,
+            height: rect.height,
+        }
+    }
+}
+
+/// Draws a line from `(x0, y0)` to `(x1, y1)`, including both ends.
+#[allow(clippy::too_many_arguments)]
+pub fn line(
+    pixels: &mut [[u8; 3]],
+    width: u32,
+    height: u32,
+    x0: i32,
+    y0: i32,
+    x1: i32,
+    y1: i32,
+    color: [u8; 3],
+) {
+    line_points(x0, y0, x1, y1, |x, y| {
+        set_pixel(pixels, width, height, x, y, color)
+    });
+}
+
+// Calls `f` with each point on the line from `(x0, y0)` to `(x1, y1)`, including both ends
+pub(crate) fn line_points(x0: i32, y0: i32, x1: i32, y1: i32, mut f: impl FnMut(i32, i32)) {
+    // Bresenham's line algorithm
+    let (dx, dy) = ((x1 - x0).abs(), -(y1 - y0).abs());
+    let (step_x, step_y) = ((x1 - x0).signum(), (y1 - y0).signum());
+    let mut error = dx + dy;
+    let (mut x, mut y) = (x0, y0);
+    loop {
+        f(x, y);
+        if x == x1 && y == y1 {
+            break;
+        }
+        if error * 2 >= dy {
+            error += dy;
+            x += step_x;
+        }
+        if error * 2 <= dx {
+            error += dx;
+            y += step_y;
+        }
+    }
+}
+
+/// An RGB image that can be drawn onto a pixel buffer.
+#[derive(PartialEq, Eq, Hash, Debug, Clone)]
+pub struct Sprite {
+    width: u32,
+    height: u32,
+    pixels: Vec<[u8; 3]>,
+}
+
+impl Sprite {
+    pub fn new(width: u32, height: u32, pixels: Vec<[u8; 3]>) -> Self {
+        assert!((width * height) as usize == pixels.len());
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    #[inline]
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+    #[inline]
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+    #[inline]
+    pub fn pixels(&self) -> &[[u8; 3]] {
+        &self.pixels
+    }
+    #[inline]
+    pub fn pixels_mut(&mut self) -> &mut [[u8; 3]] {
+        &mut self.pixels
+    }
+
+    #[inline]
+    pub fn get_pixel(&self, x: u32, y: u32) -> [u8; 3] {
+        self.pixels[(x + y * self.width) as usize]
+    }
+}
+
+/// Draws `sprite` with its top-left corner at `(x, y)`, clipped to the buffer.
+///
+/// Sprite pixels equal to `transparent` are skipped.
+pub fn blit(
+    pixels: &mut [[u8; 3]],
+    width: u32,
+    height: u32,
+    sprite: &Sprite,
+    x: i32,
+    y: i32,
+    transparent: Option<[u8; 3]>,
+) {
+    let clip = Rect::new(0, 0, width, height);
+    blit_clipped(pixels, width, clip, sprite, x, y, transparent);
+}
+
+// `blit`, but only drawing inside `clip`, which has to be inside the buffer
+pub(crate) fn blit_clipped(
+    pixels: &mut [[u8; 3]],
+    width: u32,
+    clip: Rect,
+    sprite: &Sprite,
+    x: i32,
+    y: i32,
+    transparent: Option<[u8; 3]>,
+) {
+    let rect = Rect::new(x, y, sprite.width, sprite.height).intersection(clip);
+    for dest_y in rect.y..rect.bottom() {
+        let src_row = ((dest_y - y) as u32 * sprite.width) as usize;
+        let dest_row = (dest_y as u32 * width) as usize;
+        for dest_x in rect.x..rect.right() {
+            let color = sprite.pixels[src_row + (dest_x - x) as usize];
+            if Some(color) != transparent {
+                pixels[dest_row + dest_x as usize] = color;
+            }
+        }
+    }
+}
+
+/// A depth value for each pixel of a buffer, so things can be drawn in any order and still
+/// cover each other correctly, like in a 2.5D game. Drawing through it only changes a pixel if
+/// the new depth is nearer, meaning smaller, than what's already there.
+///
+/// Clear it with [`clear`](Self::clear) whenever the pixel buffer is cleared, usually at the
+/// start of each frame.
+#[derive(PartialEq, Debug, Clone)]
+pub struct DepthBuffer {
+    width: u32,
+    height: u32,
+    depth: Vec<f32>,
+}
+
+impl DepthBuffer {
+    /// Creates a depth buffer for a pixel buffer of the given size, with nothing drawn yet.
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            depth: vec![f32::INFINITY; (width * height) as usize],
+        }
+    }
+
+    #[inline]
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+    #[inline]
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Resets every pixel to infinitely far away, so anything drawn next is in front.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.depth.fill(f32::INFINITY);
+    }
+
+    /// The depth of whatever was last drawn at `(x, y)`, or infinity if nothing was.
+    #[inline]
+    pub fn get_depth(&self, x: u32, y: u32) -> f32 {
+        self.depth[(x + y * self.width) as usize]
+    }
+
+    // Records `z` at `index` if it's nearer than what's there, returning whether it was
+    #[inline]
+    fn test_and_set(&mut self, index: usize, z: f32) -> bool {
+        let nearer = z < self.depth[index];
+        if nearer {
+            self.depth[index] = z;
+        }
+        nearer
+    }
+
+    /// Sets the pixel at `(x, y)` of `pixels` if `z` is nearer than what's already there. Does
+    /// nothing if it's outside the buffer.
+    pub fn set_pixel(&mut self, pixels: &mut [[u8; 3]], x: i32, y: i32, color: [u8; 3], z: f32) {
+        if Rect::new(0, 0, self.width, self.height).contains(x, y) {
+            let index = (x as u32 + y as u32 * self.width) as usize;
+            if self.test_and_set(index, z) {
+                pixels[index] = color;
+            }
+        }
+    }
+
+    /// Draws `sprite` at depth `z` with its top-left corner at `(x, y)`, skipping the pixels
+    /// where something nearer has already been drawn. Otherwise the same as [`blit`].
+    pub fn blit(
+        &mut self,
+        pixels: &mut [[u8; 3]],
+        sprite: &Sprite,
+        x: i32,
+        y: i32,
+        z: f32,
+        transparent: Option<[u8; 3]>,
+    ) {
+        let clip = Rect::new(0, 0, self.width, self.height);
+        self.blit_clipped(pixels, clip, sprite, x, y, z, transparent);
+    }
+
+    // `blit`, but only drawing inside `clip`, which has to be inside the buffer
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn blit_clipped(
+        &mut self,
+        pixels: &mut [[u8; 3]],
+        clip: Rect,
+        sprite: &Sprite,
+        x: i32,
+        y: i32,
+        z: f32,
+        transparent: Option<[u8; 3]>,
+    ) {
+        let rect = Rect::new(x, y, sprite.width, sprite.height).intersection(clip);
+        for dest_y in rect.y..rect.bottom() {
+            let src_row = ((dest_y - y) as u32 * sprite.width) as usize;
+            let dest_row = (dest_y as u32 * self.width) as usize;
+            for dest_x in rect.x..rect.right() {
+                let color = sprite.pixels[src_row + (dest_x - x) as usize];
+                let index = dest_row + dest_x as usize;
+                // Transparent pixels don't hide what's drawn behind them later
+                if Some(color) != transparent && self.test_and_set(index, z) {
+                    pixels[index] = color;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ON: [u8; 3] = [255; 3];
+    const OFF: [u8; 3] = [0; 3];
+
+    // The coordinates of every pixel set to `ON` in a buffer `width` pixels wide
+    fn lit(pixels: &[[u8; 3]], width: u32) -> Vec<(u32, u32)> {
+        (0..pixels.len() as u32)
+            .filter(|&i| pixels[i as usize] == ON)
+            .map(|i| (i % width, i / width))
+            .collect()
+    }
+
+    #[test]
+    fn sub_image_clips_writes_to_its_region() {
+        let mut pixels = vec![OFF; 8 * 8];
+        let mut view = SubImage::new(&mut pixels, 8, Rect::new(2, 3, 2, 2));
+        view.set_pixel(0, 0, ON);
+        view.set_pixel(1, 1, ON);
+        view.set_pixel(2, 0, ON);
+        view.set_pixel(0, 2, ON);
+        view.set_pixel(-1, 0, ON);
+        view.set_pixel(0, -1, ON);
+        assert_eq!(lit(&pixels, 8), [(2, 3), (3, 4)]);
+    }
+
+    #[test]
+    fn sub_image_clips_fills_to_its_region() {
+        let mut pixels = vec![OFF; 8 * 8];
+        let mut view = SubImage::new(&mut pixels, 8, Rect::new(2, 3, 2, 2));
+        view.fill_rect(Rect::new(-5, -5, 100, 100), ON);
+        assert_eq!(lit(&pixels, 8), [(2, 3), (3, 3), (2, 4), (3, 4)]);
+    }
+
+    #[test]
+    fn sub_image_clips_to_the_buffer() {
+        let mut pixels = vec![OFF; 4 * 4];
+        let mut view = SubImage::new(&mut pixels, 4, Rect::new(3, 3, 4, 4));
+        view.fill_rect(Rect::new(0, 0, 4, 4), ON);
+        view.set_pixel(1, 0, ON);
+        assert_eq!(lit(&pixels, 4), [(3, 3)]);
+    }
+
+    #[test]
+    fn nested_sub_images_stay_inside_their_parent() {
+        let mut pixels = vec![OFF; 8 * 8];
+        let mut view = SubImage::new(&mut pixels, 8, Rect::new(2, 2, 3, 3));
+        let mut inner = view.sub_image(Rect::new(1, 1, 5, 5));
+        inner.fill_rect(Rect::new(0, 0, 5, 5), ON);
+        assert_eq!(lit(&pixels, 8), [(3, 3), (4, 3), (3, 4), (4, 4)]);
+    }
+}
@@ -0,0 +1,434 @@
+use alloc::vec::Vec;
+
+use crate::raster::Rect;
+
+// 3x5 glyphs for ' '..='~', one row per 3 bits with the top row in the highest bits.
+// Lowercase letters share the uppercase shapes.
+#[rustfmt::skip]
+const DEFAULT_GLYPHS: [u16; 95] = [
+    0b000_000_000_000_000, // ' '
+    0b010_010_010_000_010, // '!'
+    0b101_101_000_000_000, // '"'
+    0b101_111_101_111_101, // '#'
+    0b011_110_010_011_110, // '$'
+    0b101_001_010_100_101, // '%'
+    0b010_101_010_101_011, // '&'
+    0b010_010_000_000_000, // '''
+    0b001_010_010_010_001, // '('
+    0b100_010_010_010_100, // ')'
+    0b000_101_010_101_000, // '*'
+    0b000_010_111_010_000, // '+'
+    0b000_000_000_010_100, // ','
+    0b000_000_111_000_000, // '-'
+    0b000_000_000_000_010, // '.'
+    0b001_001_010_100_100, // '/'
+    0b111_101_101_101_111, // '0'
+    0b010_110_010_010_111, // '1'
+    0b111_001_111_100_111, // '2'
+    0b111_001_111_001_111, // '3'
+    0b101_101_111_001_001, // '4'
+    0b111_100_111_001_111, // '5'
+    0b111_100_111_101_111, // '6'
+    0b111_001_001_001_001, // '7'
+    0b111_101_111_101_111, // '8'
+    0b111_101_111_001_111, // '9'
+    0b000_010_000_010_000, // ':'
+    0b000_010_000_010_100, // ';'
+    0b001_010_100_010_001, // '<'
+    0b000_111_000_111_000, // '='
+    0b100_010_001_010_100, // '>'
+    0b111_001_010_000_010, // '?'
+    0b010_101_111_100_011, // '@'
+    0b010_101_111_101_101, // 'A'
+    0b110_101_110_101_110, // 'B'
+    0b011_100_100_100_011, // 'C'
+    0b110_101_101_101_110, // 'D'
+    0b111_100_110_100_111, // 'E'
+    0b111_100_110_100_100, // 'F'
+    0b011_100_101_101_011, // 'G'
+    0b101_101_111_101_101, // 'H'
+    0b111_010_010_010_111, // 'I'
+    0b001_001_001_101_010, // 'J'
+    0b101_101_110_101_101, // 'K'
+    0b100_100_100_100_111, // 'L'
+    0b101_111_111_101_101, // 'M'
+    0b110_101_101_101_101, // 'N'
+    0b010_101_101_101_010, // 'O'
+    0b110_101_110_100_100, // 'P'
+    0b010_101_101_110_011, // 'Q'
+    0b110_101_110_101_101, // 'R'
+    0b011_100_010_001_110, // 'S'
+    0b111_010_010_010_010, // 'T'
+    0b101_101_101_101_111, // 'U'
+    0b101_101_101_101_010, // 'V'
+    0b101_101_111_111_101, // 'W'
+    0b101_101_010_101_101, // 'X'
+    0b101_101_010_010_010, // 'Y'
+    0b111_001_010_100_111, // 'Z'
+    0b110_100_100_100_110, // '['
+    0b100_100_010_001_001, // '\'
+    0b011_001_001_001_011, // ']'
+    0b010_101_000_000_000, // '^'
+    0b000_000_000_000_111, // '_'
+    0b100_010_000_000_000, // '`'
+    0b010_101_111_101_101, // 'a'
+    0b110_101_110_101_110, // 'b'
+    0b011_100_100_100_011, // 'c'
+    0b110_101_101_101_110, // 'd'
+    0b111_100_110_100_111, // 'e'
+    0b111_100_110_100_100, // 'f'
+    0b011_100_101_101_011, // 'g'
+    0b101_101_111_101_101, // 'h'
+    0b111_010_010_010_111, // 'i'
+    0b001_001_001_101_010, // 'j'
+    0b101_101_110_101_101, // 'k'
+    0b100_100_100_100_111, // 'l'
+    0b101_111_111_101_101, // 'm'
+    0b110_101_101_101_101, // 'n'
+    0b010_101_101_101_010, // 'o'
+    0b110_101_110_100_100, // 'p'
+    0b010_101_101_110_011, // 'q'
+    0b110_101_110_101_101, // 'r'
+    0b011_100_010_001_110, // 's'
+    0b111_010_010_010_010, // 't'
+    0b101_101_101_101_111, // 'u'
+    0b101_101_101_101_010, // 'v'
+    0b101_101_111_111_101, // 'w'
+    0b101_101_010_101_101, // 'x'
+    0b101_101_010_010_010, // 'y'
+    0b111_001_010_100_111, // 'z'
+    0b011_010_110_010_011, // '{'
+    0b010_010_010_010_010, // '|'
+    0b110_010_011_010_110, // '}'
+    0b000_011_110_000_000, // '~'
+];
+
+pub struct Font {
+    glyph_width: u32,
+    glyph_height: u32,
+    first_char: u32,
+    glyph_count: u32,
+    // One entry per pixel, glyphs stored one after another
+    mask: Vec<bool>,
+    // Per-glyph advance widths for proportional fonts
+    advance_widths: Option<Vec<u32>>,
+
+    tracking: u32,
+    line_spacing: u32,
+}
+
+impl Font {
+    /// Creates a font from a vertical strip of glyphs.
+    ///
+    /// `sheet` is a `glyph_w` pixels wide mask image with one byte per pixel, where any nonzero
+    /// byte is part of a glyph. Glyphs are stacked top to bottom and map to consecutive
+    /// characters, starting with `first_char`.
+    pub fn from_grid(sheet: &[u8], glyph_w: u32, glyph_h: u32, first_char: char) -> Self {
+        let glyph_size = (glyph_w * glyph_h) as usize;
+        assert!(glyph_size != 0);
+        let glyph_count = sheet.len() / glyph_size;
+        assert!(glyph_count * glyph_size == sheet.len());
+        Self {
+            glyph_width: glyph_w,
+            glyph_height: glyph_h,
+            first_char: first_char as u32,
+            glyph_count: glyph_count as u32,
+            mask: sheet.iter().map(|&x| x != 0).collect(),
+            advance_widths: None,
+
+            tracking: 1,
+            line_spacing: 1,
+        }
+    }
+
+    /// Makes the font proportional, giving each glyph its own advance width instead of
+    /// `glyph_w`. `widths` has one entry per glyph, in the same order as the sheet.
+    ///
+    /// Glyph pixels past a glyph's advance width are still drawn.
+    #[inline]
+    pub fn advance_widths(mut self, widths: Vec<u32>) -> Self {
+        assert!(widths.len() == self.glyph_count as usize);
+        self.advance_widths = Some(widths);
+        self
+    }
+
+    /// Sets the number of blank pixels between adjacent glyphs. Defaults to 1.
+    #[inline]
+    pub fn tracking(mut self, tracking: u32) -> Self {
+        self.tracking = tracking;
+        self
+    }
+
+    #[inline]
+    pub fn glyph_width(&self) -> u32 {
+        self.glyph_width
+    }
+    #[inline]
+    pub fn glyph_height(&self) -> u32 {
+        self.glyph_height
+    }
+
+    fn glyph_index(&self, char: char) -> Option<u32> {
+        let index = (char as u32).checked_sub(self.first_char)?;
+        (index < self.glyph_count).then_some(index)
+    }
+
+    // Characters outside the font are drawn as '?', or skipped if the font doesn't have one
+    fn glyph(&self, char: char) -> Option<u32> {
+        self.glyph_index(char).or_else(|| self.glyph_index('?'))
+    }
+
+    fn glyph_mask(&self, index: u32) -> &[bool] {
+        let glyph_size = (self.glyph_width * self.glyph_height) as usize;
+        &self.mask[index as usize * glyph_size..][..glyph_size]
+    }
+
+    fn advance_width(&self, glyph: Option<u32>) -> u32 {
+        match (&self.advance_widths, glyph) {
+            (Some(widths), Some(index)) => widths[index as usize],
+            _ => self.glyph_width,
+        }
+    }
+
+    fn line_width(&self, line: &str) -> u32 {
+        let mut width = 0;
+        let mut chars = 0;
+        for char in line.chars() {
+            width += self.advance_width(self.glyph(char));
+            chars += 1;
+        }
+        if chars == 0 {
+            return 0;
+        }
+        width + (chars - 1) * self.tracking
+    }
+
+    fn line_advance(&self) -> i32 {
+        (self.glyph_height + self.line_spacing) as i32
+    }
+
+    fn draw_line(
+        &self,
+        pixels: &mut [[u8; 3]],
+        width: u32,
+        clip: Rect,
+        (x, y): (i32, i32),
+        line: &str,
+        color: [u8; 3],
+    ) {
+        let mut glyph_x = x;
+        for char in line.chars() {
+            let glyph = self.glyph(char);
+            if let Some(index) = glyph {
+                let mask = self.glyph_mask(index);
+                for gy in 0..self.glyph_height {
+                    for gx in 0..self.glyph_width {
+                        if !mask[(gx + gy * self.glyph_width) as usize] {
+                            continue;
+                        }
+                        let (px, py) = (glyph_x + gx as i32, y + gy as i32);
+                        if clip.contains(px, py) {
+                            pixels[(px + py * width as i32) as usize] = color;
+                        }
+                    }
+                }
+            }
+            glyph_x += (self.advance_width(glyph) + self.tracking) as i32;
+        }
+    }
+}
+
+impl Default for Font {
+    /// The built-in 3x5 font, covering printable ASCII.
+    fn default() -> Self {
+        let mut sheet = Vec::with_capacity(DEFAULT_GLYPHS.len() * 15);
+        for glyph in DEFAULT_GLYPHS {
+            sheet.extend((0..15).rev().map(|bit| (glyph >> bit & 1) as u8));
+        }
+        Self::from_grid(&sheet, 3, 5, ' ')
+    }
+}
+
+/// Returns the size in pixels of the box that `text` occupies when drawn with [`draw_text`].
+///
+/// Lines are separated by `'\n'`; the width is that of the longest line.
+pub fn measure_text(font: &Font, text: &str) -> (u32, u32) {
+    if text.is_empty() {
+        return (0, 0);
+    }
+    let mut width = 0;
+    let mut lines = 0;
+    for line in text.split('\n') {
+        width = width.max(font.line_width(line));
+        lines += 1;
+    }
+    (
+        width,
+        lines * font.glyph_height + (lines - 1) * font.line_spacing,
+    )
+}
+
+/// Draws `text` with its top-left corner at `(x, y)`.
+///
+/// Glyphs are clipped to the buffer. Characters that the font doesn't cover are drawn as `'?'`.
+#[allow(clippy::too_many_arguments)]
+pub fn draw_text(
+    pixels: &mut [[u8; 3]],
+    width: u32,
+    height: u32,
+    font: &Font,
+    x: i32,
+    y: i32,
+    text: &str,
+    color: [u8; 3],
+) {
+    let clip = Rect::new(0, 0, width, height);
+    draw_text_clipped(pixels, width, clip, font, x, y, text, color);
+}
+
+// `draw_text`, but only drawing inside `clip`, which has to be inside the buffer
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn draw_text_clipped(
+    pixels: &mut [[u8; 3]],
+    width: u32,
+    clip: Rect,
+    font: &Font,
+    x: i32,
+    y: i32,
+    text: &str,
+    color: [u8; 3],
+) {
+    let mut line_y = y;
+    for line in text.split('\n') {
+        font.draw_line(pixels, width, clip, (x, line_y), line, color);
+        line_y += font.line_advance();
+    }
+}
+
+/// Draws `text` centered on `(cx, cy)`. Each line is centered horizontally on its own.
+#[allow(clippy::too_many_arguments)]
+pub fn draw_text_centered(
+    pixels: &mut [[u8; 3]],
+    width: u32,
+    height: u32,
+    font: &Font,
+    cx: i32,
+    cy: i32,
+    text: &str,
+    color: [u8; 3],
+) {
+    let clip = Rect::new(0, 0, width, height);
+    draw_text_centered_clipped(pixels, width, clip, font, cx, cy, text, color);
+}
+
+// `draw_text_centered`, but only drawing inside `clip`, which has to be inside the buffer
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn draw_text_centered_clipped(
+    pixels: &mut [[u8; 3]],
+    width: u32,
+    clip: Rect,
+    font: &Font,
+    cx: i32,
+    cy: i32,
+    text: &str,
+    color: [u8; 3],
+) {
+    let (_, text_height) = measure_text(font, text);
+    let mut line_y = cy - text_height as i32 / 2;
+    for line in text.split('\n') {
+        let line_x = cx - font.line_width(line) as i32 / 2;
+        font.draw_line(pixels, width, clip, (line_x, line_y), line, color);
+        line_y += font.line_advance();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn measures_glyph_widths_plus_tracking() {
+        let font = Font::default();
+        assert_eq!(measure_text(&font, "hello"), (5 * 3 + 4, 5));
+        assert_eq!(measure_text(&font, "a"), (3, 5));
+        assert_eq!(measure_text(&font, ""), (0, 0));
+
+        let font = Font::default().tracking(2);
+        assert_eq!(measure_text(&font, "hello"), (5 * 3 + 4 * 2, 5));
+    }
+
+    #[test]
+    fn measures_the_longest_line() {
+        let font = Font::default();
+        assert_eq!(measure_text(&font, "ab\nabcd\nc"), (4 * 3 + 3, 3 * 5 + 2));
+    }
+
+    #[test]
+    fn centers_text_on_a_point() {
+        let font = Font::default();
+        let mut pixels = vec![[0; 3]; 9 * 5];
+        // "|" is a single column in the middle of its glyph
+        draw_text_centered(&mut pixels, 9, 5, &font, 4, 2, "|", [255; 3]);
+        for y in 0..5 {
+            for x in 0..9 {
+                let expected = if x == 4 { [255; 3] } else { [0; 3] };
+                assert_eq!(pixels[x + y * 9], expected, "pixel ({x}, {y})");
+            }
+        }
+    }
+
+    // Two 2x2 glyphs: 'a' is a diagonal and 'b' is filled in
+    fn two_glyph_font() -> Font {
+        Font::from_grid(&[1, 0, 0, 1, 1, 1, 1, 1], 2, 2, 'a')
+    }
+
+    #[test]
+    fn draws_from_a_custom_font() {
+        let font = two_glyph_font();
+        let mut pixels = vec![[0; 3]; 5 * 2];
+        draw_text(&mut pixels, 5, 2, &font, 0, 0, "ab", [255; 3]);
+        let on = [255; 3];
+        let off = [0; 3];
+        #[rustfmt::skip]
+        let expected = [
+            on, off, off, on, on,
+            off, on, off, on, on,
+        ];
+        assert_eq!(pixels, expected);
+    }
+
+    #[test]
+    fn draws_unknown_characters_as_nothing_without_a_question_mark() {
+        let font = two_glyph_font();
+        let mut pixels = vec![[0; 3]; 5 * 2];
+        draw_text(&mut pixels, 5, 2, &font, 0, 0, "zz", [255; 3]);
+        assert!(pixels.iter().all(|&pixel| pixel == [0; 3]));
+        // Still takes up room, so the layout doesn't shift around missing glyphs
+        assert_eq!(measure_text(&font, "zz"), (5, 2));
+    }
+
+    #[test]
+    fn measures_proportional_text() {
+        let font = two_glyph_font().advance_widths(vec![1, 2]);
+        // 1 + 2 + 1 + 1 wide glyphs with a pixel of tracking between each
+        assert_eq!(measure_text(&font, "abaa"), (1 + 2 + 1 + 1 + 3, 2));
+
+        let font = two_glyph_font().advance_widths(vec![1, 2]).tracking(0);
+        assert_eq!(measure_text(&font, "abaa"), (5, 2));
+    }
+
+    #[test]
+    fn advances_proportional_glyphs_by_their_own_width() {
+        let font = two_glyph_font().advance_widths(vec![1, 2]).tracking(0);
+        let mut pixels = vec![[0; 3]; 4 * 2];
+        draw_text(&mut pixels, 4, 2, &font, 0, 0, "ba", [255; 3]);
+        let on = [255; 3];
+        let off = [0; 3];
+        #[rustfmt::skip]
+        let expected = [
+            on, on, on, off,
+            on, on, off, on,
+        ];
+        assert_eq!(pixels, expected);
+    }
+}
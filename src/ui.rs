@@ -0,0 +1,158 @@
+use crate::{
+    draw::{Canvas, Rect},
+    text::{self, Font},
+    Context, MouseButton,
+};
+
+const TEXT_COLOR: [u8; 3] = [255, 255, 255];
+const BUTTON_COLOR: [u8; 3] = [96, 96, 96];
+const BUTTON_HOVERED_COLOR: [u8; 3] = [128, 128, 128];
+const BUTTON_PRESSED_COLOR: [u8; 3] = [64, 64, 64];
+const BUTTON_SHADOW_COLOR: [u8; 3] = [32, 32, 32];
+
+/// Immediate-mode UI widgets.
+///
+/// Create a `Ui` at the start of each frame and call its widgets while drawing; each widget
+/// draws itself and reports whether it was clicked this frame, so there's no widget state to
+/// keep around.
+pub struct Ui<'a> {
+    font: &'a Font,
+
+    mouse_pos: (i32, i32),
+    is_mouse_down: bool,
+    is_mouse_clicked: bool,
+}
+
+impl<'a> Ui<'a> {
+    pub fn new(ctx: &Context, font: &'a Font) -> Self {
+        // Widgets are laid out in the same space the canvas draws in, which is the logical size
+        // if there is one
+        Self::with_mouse(
+            font,
+            crate::mouse_pixel(ctx.logical_mouse_pos()),
+            ctx.is_mouse_button_pressed(MouseButton::Left),
+            ctx.is_mouse_button_just_pressed(MouseButton::Left),
+        )
+    }
+
+    fn with_mouse(
+        font: &'a Font,
+        mouse_pos: (i32, i32),
+        is_mouse_down: bool,
+        is_mouse_clicked: bool,
+    ) -> Self {
+        Self {
+            font,
+
+            mouse_pos,
+            is_mouse_down,
+            is_mouse_clicked,
+        }
+    }
+
+    #[inline]
+    fn is_hovered(&self, rect: Rect) -> bool {
+        rect.contains(self.mouse_pos.0, self.mouse_pos.1)
+    }
+
+    /// Draws a button with `label` centered on it. Returns true on the frame the left mouse
+    /// button is pressed over it.
+    pub fn button(&mut self, canvas: &mut Canvas, rect: Rect, label: &str) -> bool {
+        let hovered = self.is_hovered(rect);
+        let pressed = hovered && self.is_mouse_down;
+
+        // The button sits on a one-pixel shadow and sinks into it while pressed
+        let face = Rect::new(
+            rect.x,
+            rect.y + pressed as i32,
+            rect.width,
+            rect.height.saturating_sub(1),
+        );
+        let shadow = Rect::new(rect.x, rect.bottom() - 1, rect.width, 1);
+        canvas.fill_rect(shadow, BUTTON_SHADOW_COLOR);
+        canvas.fill_rect(
+            face,
+            if pressed {
+                BUTTON_PRESSED_COLOR
+            } else if hovered {
+                BUTTON_HOVERED_COLOR
+            } else {
+                BUTTON_COLOR
+            },
+        );
+        canvas.text_centered(
+            self.font,
+            face.x + face.width as i32 / 2,
+            face.y + face.height as i32 / 2,
+            label,
+            TEXT_COLOR,
+        );
+
+        hovered && self.is_mouse_clicked
+    }
+
+    /// Draws `text` with its top-left corner at `(x, y)`.
+    pub fn label(&mut self, canvas: &mut Canvas, x: i32, y: i32, text: &str) {
+        canvas.text(self.font, x, y, text, TEXT_COLOR);
+    }
+
+    /// Draws a checkbox with its top-left corner at `(x, y)`, followed by `label`. Clicking the
+    /// box or the label flips `checked`. Returns true if it was flipped this frame.
+    pub fn checkbox(
+        &mut self,
+        canvas: &mut Canvas,
+        x: i32,
+        y: i32,
+        label: &str,
+        checked: &mut bool,
+    ) -> bool {
+        let box_size = self.font.glyph_height() + 2;
+        let (label_width, _) = text::measure_text(self.font, label);
+        let hit_rect = Rect::new(x, y, box_size + 2 + label_width, box_size);
+
+        let hovered = self.is_hovered(hit_rect);
+        let clicked = hovered && self.is_mouse_clicked;
+        if clicked {
+            *checked = !*checked;
+        }
+
+        let box_rect = Rect::new(x, y, box_size, box_size);
+        canvas.fill_rect(
+            box_rect,
+            if hovered {
+                BUTTON_HOVERED_COLOR
+            } else {
+                BUTTON_COLOR
+            },
+        );
+        if *checked {
+            // Fonts can be a single pixel tall, which leaves no room for the mark
+            let mark_size = box_size.saturating_sub(4);
+            canvas.fill_rect(Rect::new(x + 2, y + 2, mark_size, mark_size), TEXT_COLOR);
+        }
+        canvas.text(self.font, x + box_size as i32 + 2, y + 1, label, TEXT_COLOR);
+
+        clicked
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn widgets_are_hit_tested_in_logical_coordinates() {
+        // A 64x64 buffer drawn at a logical size of 32x32, with the mouse at (41, 41) in the buffer
+        let font = Font::default();
+        let mouse_pos = crate::to_logical((41.0, 41.0), (64, 64), Some((32, 32)));
+        let mut pixels = vec![[0; 3]; 64 * 64];
+        let mut canvas = Canvas::new(&mut pixels, 64, 64).with_logical_size(32, 32);
+        let mut ui = Ui::with_mouse(&font, crate::mouse_pixel(mouse_pos), true, true);
+
+        // Where the mouse would be without the logical size
+        assert!(!ui.button(&mut canvas, Rect::new(40, 40, 8, 8), ""));
+        assert!(ui.button(&mut canvas, Rect::new(16, 16, 8, 8), ""));
+        // Drawn pressed under the cursor
+        assert_eq!(pixels[41 * 64 + 41], BUTTON_PRESSED_COLOR);
+    }
+}